@@ -159,12 +159,11 @@ void show_LEDs_hex(const char *hexes) {
   pixels.show();
 }
 
-void show_LEDs(uint8_t *rgb) {
-  for (int i = 0; i < CIRCLE_SIZE; i++) {
-    // int j = (i + CIRCLE_SIZE / 2) % CIRCLE_SIZE;
-    int j = CIRCLE_SIZE - i;
-    pixels.setPixelColor(j + FIRST_PIXEL,
-                         pixels.Color(rgb[i*3], rgb[i*3+1], rgb[i*3+2]));
+// The server already sends the pixels in the order of the strip,
+// see the LED mapping in the README.
+void show_LEDs(uint8_t *rgb, int count) {
+  for (int i = 0; i < count && i <= NUMPIXELS; i++) {
+    pixels.setPixelColor(i, pixels.Color(rgb[i*3], rgb[i*3+1], rgb[i*3+2]));
   }
   pixels.show();
 }
@@ -203,13 +202,13 @@ void state_udp_read() {
     }
     delay(10);
   }
-  int bufLen = CIRCLE_SIZE * 3;
+  int bufLen = (NUMPIXELS + 1) * 3;
   uint8_t buf[bufLen + 1];
   int res = client_udp.read(buf, bufLen);
-  if (res != bufLen) {
+  if (res < CIRCLE_SIZE * 3) {
     Serial.printf("%06ld (%03d): Only got %d out of %d bytes\n", millis(), millis() - last, res, bufLen);
   } else {
-    show_LEDs(buf);
+    show_LEDs(buf, res / 3);
   }
   // buf[10] = 0;
   // Serial.printf("%06ld: Read %d bytes, starting with: %s\n", millis() - last, res, buf);
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 use strum_macros::{Display, EnumString};
 
@@ -5,56 +7,260 @@ pub const LED_COUNT: usize = 288;
 
 pub const FREQUENCY: usize = 50;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Display, EnumString, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[strum(ascii_case_insensitive)]
 pub enum Game {
     Idle,
     Snake,
     Drop,
+    Pong,
+    Whack,
+    Simon,
+    Replay,
+    Off,
 }
 
-#[derive(Display, EnumString, Clone, PartialEq, Debug, Deserialize, Serialize, Hash, Eq, Copy)]
-pub enum PlayColor {
-    Red,
-    Green,
-    Blue,
-    Yellow,
-    Cyan,
-    Magenta,
+impl Game {
+    pub fn all() -> Vec<Game> {
+        vec![
+            Self::Idle,
+            Self::Snake,
+            Self::Drop,
+            Self::Pong,
+            Self::Whack,
+            Self::Simon,
+            Self::Replay,
+            Self::Off,
+        ]
+    }
+}
+
+/// The current game, the game which was stopped for lack of activity, if any,
+/// and whether the schedule lets the phones choose a game.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GameStatus {
+    pub game: Game,
+    pub timed_out: Option<Game>,
+    pub games_allowed: bool,
+}
+
+/// Answer of the rate limited server functions: either the answer of the game,
+/// or the milliseconds to wait before the next request is accepted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Limited<T> {
+    Accepted(T),
+    Cooldown(u64),
+}
+
+/// Up to this many players can join a game.
+pub const MAX_PLAYERS: usize = 12;
+
+/// Colors closer than this on the color wheel, in degrees, can't be told apart on the LEDs.
+const MIN_HUE_DISTANCE: f32 = 20.;
+/// Whitish or dark colors can't be told apart from the obstacles or the background.
+const MIN_SATURATION: f32 = 0.6;
+const MIN_VALUE: f32 = 0.6;
+
+/// The color of a player, either from the palette or chosen on the color wheel.
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize, Hash, Eq, Copy)]
+pub struct PlayColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
 }
 
+/// The palette are the hues of the color wheel every 30 degrees, starting with the original six colors.
+const PALETTE: [(&str, PlayColor); MAX_PLAYERS] = [
+    ("Red", PlayColor::RED),
+    ("Green", PlayColor::rgb(0x00, 0xff, 0x00)),
+    ("Blue", PlayColor::rgb(0x00, 0x00, 0xff)),
+    ("Yellow", PlayColor::rgb(0xff, 0xff, 0x00)),
+    ("Cyan", PlayColor::rgb(0x00, 0xff, 0xff)),
+    ("Magenta", PlayColor::rgb(0xff, 0x00, 0xff)),
+    ("Orange", PlayColor::rgb(0xff, 0x80, 0x00)),
+    ("Lime", PlayColor::rgb(0x80, 0xff, 0x00)),
+    ("Spring", PlayColor::rgb(0x00, 0xff, 0x80)),
+    ("Azure", PlayColor::rgb(0x00, 0x80, 0xff)),
+    ("Violet", PlayColor::rgb(0x80, 0x00, 0xff)),
+    ("Rose", PlayColor::rgb(0xff, 0x00, 0x80)),
+];
+
 impl PlayColor {
-    pub fn to_hex(&self) -> String {
+    pub const RED: PlayColor = PlayColor::rgb(0xff, 0x00, 0x00);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Parses `rrggbb`, with an optional leading `#`, as sent by the color wheel.
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let rgb = hex::decode(hex.trim_start_matches('#')).ok()?;
+        match rgb.as_slice() {
+            [r, g, b] => Some(Self::rgb(*r, *g, *b)),
+            _ => None,
+        }
+    }
+
+    pub fn to_hex(self) -> String {
+        format!("{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+
+    pub fn to_hex_pastel(self) -> String {
+        let pastel = |c: u8| ((c as u16 + 0x110) / 2).min(0xff) as u8;
+        Self::rgb(pastel(self.r), pastel(self.g), pastel(self.b)).to_hex()
+    }
+
+    /// Mixes like light: the colors are averaged with the `weight` of `other`,
+    /// then brightened so red and green give yellow.
+    pub fn mix(&self, other: &PlayColor, weight: f32) -> PlayColor {
+        let avg = |a: u8, b: u8| a as f32 * (1. - weight) + b as f32 * weight;
+        let (r, g, b) = (
+            avg(self.r, other.r),
+            avg(self.g, other.g),
+            avg(self.b, other.b),
+        );
+        let scale = 255. / r.max(g).max(b).max(1.);
+        Self::rgb(
+            (r * scale).round() as u8,
+            (g * scale).round() as u8,
+            (b * scale).round() as u8,
+        )
+    }
+
+    pub fn all() -> Vec<PlayColor> {
+        PALETTE.iter().map(|(_, c)| *c).collect()
+    }
+
+    /// The original six colors, for the games with one button per color.
+    pub fn basic() -> Vec<PlayColor> {
+        Self::all().into_iter().take(6).collect()
+    }
+
+    /// Returns the hue in degrees, the saturation and the value.
+    fn hsv(&self) -> (f32, f32, f32) {
+        let (r, g, b) = (
+            self.r as f32 / 255.,
+            self.g as f32 / 255.,
+            self.b as f32 / 255.,
+        );
+        let max = r.max(g).max(b);
+        let delta = max - r.min(g).min(b);
+        let hue = if delta == 0. {
+            0.
+        } else if max == r {
+            60. * ((g - b) / delta).rem_euclid(6.)
+        } else if max == g {
+            60. * ((b - r) / delta + 2.)
+        } else {
+            60. * ((r - g) / delta + 4.)
+        };
+        let saturation = if max == 0. { 0. } else { delta / max };
+        (hue, saturation, max)
+    }
+
+    /// The color is bright and saturated enough to be shown as a player.
+    pub fn is_visible(&self) -> bool {
+        let (_, saturation, value) = self.hsv();
+        saturation >= MIN_SATURATION && value >= MIN_VALUE
+    }
+
+    /// Both colors can be told apart on the LEDs.
+    pub fn distinguishable(&self, other: &PlayColor) -> bool {
+        let distance = (self.hsv().0 - other.hsv().0).abs();
+        distance.min(360. - distance) >= MIN_HUE_DISTANCE
+    }
+
+    /// A new player with this color can join the other players.
+    pub fn can_join(&self, players: &[PlayColor]) -> bool {
+        players.len() < MAX_PLAYERS
+            && self.is_visible()
+            && players.iter().all(|p| p.distinguishable(self))
+    }
+}
+
+/// The colors of the palette have a name, the others are shown as hex.
+impl fmt::Display for PlayColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match PALETTE.iter().find(|(_, c)| c == self) {
+            Some((name, _)) => write!(f, "{name}"),
+            None => write!(f, "#{}", self.to_hex()),
+        }
+    }
+}
+
+/// Nicknames are cut to this many characters.
+const NICKNAME_LENGTH: usize = 16;
+
+/// The nicknames of the players, falling back to the name of their color.
+#[derive(Clone, PartialEq, Debug, Default, Deserialize, Serialize)]
+pub struct Nicknames(pub Vec<(PlayColor, String)>);
+
+impl Nicknames {
+    pub fn get(&self, color: PlayColor) -> String {
+        self.0
+            .iter()
+            .find(|(c, _)| c == &color)
+            .map_or(color.to_string(), |(_, name)| name.clone())
+    }
+
+    /// The nickname chosen by the player, without the fallback to the color.
+    pub fn chosen(&self, color: PlayColor) -> Option<String> {
+        self.0
+            .iter()
+            .find(|(c, _)| c == &color)
+            .map(|(_, name)| name.clone())
+    }
+
+    /// Stores the trimmed and shortened nickname, if it's not empty.
+    pub fn set(&mut self, color: PlayColor, name: &str) {
+        let name: String = name.trim().chars().take(NICKNAME_LENGTH).collect();
+        self.0.retain(|(c, _)| c != &color);
+        if !name.is_empty() {
+            self.0.push((color, name));
+        }
+    }
+
+    pub fn join(&self, colors: &[PlayColor], sep: &str) -> String {
+        colors
+            .iter()
+            .map(|c| self.get(*c))
+            .collect::<Vec<String>>()
+            .join(sep)
+    }
+}
+
+/// The teams of the snake team mode.
+#[derive(Display, EnumString, Clone, PartialEq, Debug, Deserialize, Serialize, Hash, Eq, Copy)]
+pub enum Team {
+    One,
+    Two,
+    Three,
+}
+
+impl Team {
+    pub fn to_hex(self) -> String {
         match self {
-            PlayColor::Red => "ff0000",
-            PlayColor::Green => "00ff00",
-            PlayColor::Blue => "0000ff",
-            PlayColor::Yellow => "ffff00",
-            PlayColor::Cyan => "00ffff",
-            PlayColor::Magenta => "ff00ff",
+            Team::One => "ff8800",
+            Team::Two => "8800ff",
+            Team::Three => "00ff88",
         }
         .into()
     }
 
-    pub fn to_hex_pastel(&self) -> String {
+    pub fn name(&self) -> String {
         match self {
-            PlayColor::Red => "ff8888",
-            PlayColor::Green => "88ff88",
-            PlayColor::Blue => "8888ff",
-            PlayColor::Yellow => "ffff88",
-            PlayColor::Cyan => "88ffff",
-            PlayColor::Magenta => "ff88ff",
+            Team::One => "Équipe 1",
+            Team::Two => "Équipe 2",
+            Team::Three => "Équipe 3",
         }
         .into()
     }
 
-    pub fn all() -> Vec<PlayColor> {
-        vec![
-            Self::Red,
-            Self::Green,
-            Self::Blue,
-            Self::Yellow,
-            Self::Cyan,
-            Self::Magenta,
-        ]
+    /// Returns the first `count` teams.
+    pub fn all(count: usize) -> Vec<Team> {
+        vec![Self::One, Self::Two, Self::Three]
+            .into_iter()
+            .take(count)
+            .collect()
     }
 }
@@ -0,0 +1,17 @@
+use std::{env, fmt::Debug, str::FromStr};
+
+/// Reads and parses the environment variable `name`.
+/// Returns None if it is not set or cannot be parsed, the latter being logged.
+pub fn env_parse<T: FromStr>(name: &str) -> Option<T>
+where
+    T::Err: Debug,
+{
+    let value = env::var(name).ok()?;
+    match value.trim().parse() {
+        Ok(v) => Some(v),
+        Err(e) => {
+            tracing::error!("Couldn't parse {name}={value}: {e:?}");
+            None
+        }
+    }
+}
@@ -1,19 +1,48 @@
-use std::f32::consts::TAU;
+use std::{collections::BTreeMap, f32::consts::TAU, fmt};
+
+use strum_macros::EnumString;
 
 use crate::{
-    common::{PlayColor, FREQUENCY, LED_COUNT},
-    games::snake_board::{Player, Position},
+    common::{PlayColor, Team, FREQUENCY, LED_COUNT, MAX_PLAYERS},
+    games::snake_board::{ObstacleKind, Pickup, Player},
 };
 
 const BLINK_JUMP: usize = FREQUENCY / 4;
 const BLINK_RECOVER: usize = BLINK_JUMP * 2;
+const BLINK_PICKUP: usize = FREQUENCY / 5;
+const PROJECTILE_TAIL: usize = 4;
 const FLOW_PERIOD: usize = 100;
 const FLOW_MERGE: usize = FLOW_PERIOD / 3;
 const FLOWS: [f32; 8] = [0.9, 0.8, 0.6, 0.4, 1.0, 0.3, 0.6, 0.7];
 
+/// How the LEDs of a layer are combined with the LEDs below them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Blend {
+    Replace,
+    Add,
+    Multiply,
+    Alpha(f32),
+    Xor,
+    Max,
+}
+
+/// The layers drawn on top of the background, ordered from bottom to top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Layer {
+    Entities,
+    Overlay,
+    Filter,
+}
+
+/// The background is kept in `leds` from one frame to the next, so effects like
+/// `rainbow` can build on the previous frame.
+/// The layers are cleared on every tick, and `flatten` combines everything
+/// into the `frame` which is sent to the LEDs.
 #[derive(Debug)]
 pub struct Display {
     leds: Vec<LED>,
+    layers: BTreeMap<Layer, Canvas>,
+    frame: Vec<LED>,
     pub counter: usize,
 }
 
@@ -21,24 +50,56 @@ impl Display {
     pub fn new() -> Self {
         Self {
             leds: (0..LED_COUNT).map(|_| LED::black()).collect(),
+            layers: [
+                (Layer::Entities, Canvas::new(Blend::Replace)),
+                (Layer::Overlay, Canvas::new(Blend::Max)),
+            ]
+            .into_iter()
+            .collect(),
+            frame: (0..LED_COUNT).map(|_| LED::black()).collect(),
             counter: 0,
         }
     }
 
     pub fn get_circle(&self) -> String {
-        self.leds
+        self.frame
             .iter()
             .map(|l| l.to_string())
             .collect::<Vec<String>>()
             .join("")
     }
 
-    pub fn draw_blobs(&mut self, blobs: Vec<Blob>) {
+    pub fn draw_blobs(&mut self, layer: Layer, blobs: Vec<Blob>) {
+        let counter = self.counter;
+        let canvas = self.canvas(layer);
         for blob in blobs {
-            blob.draw(self.counter, &mut self.leds);
+            blob.draw(counter, canvas);
+        }
+    }
+
+    pub fn set_blend(&mut self, layer: Layer, blend: Blend) {
+        self.canvas(layer).blend = blend;
+    }
+
+    /// Combines the background with all layers in their z-order.
+    /// Must be called once at the end of every frame.
+    pub fn flatten(&mut self) {
+        self.frame = self.leds.clone();
+        for canvas in self.layers.values() {
+            for (i, led) in canvas.leds.iter().enumerate() {
+                if let Some(led) = led {
+                    self.frame[i] = self.frame[i].blend(*led, canvas.blend);
+                }
+            }
         }
     }
 
+    fn canvas(&mut self, layer: Layer) -> &mut Canvas {
+        self.layers
+            .entry(layer)
+            .or_insert_with(|| Canvas::new(Blend::Replace))
+    }
+
     pub fn rainbow(&mut self) {
         self.leds[self.counter % LED_COUNT] = LED::from_hue((self.counter % 192) as u8);
         self.leds = (0..self.leds.len())
@@ -79,18 +140,40 @@ impl Display {
         self.leds.iter_mut().for_each(|led| *led = LED::black());
     }
 
-    pub fn game_winner(&mut self, winner: PlayColor, counter: usize) {
+    pub fn game_winner(&mut self, winner: impl Into<LED>, counter: usize) {
         let bright = ((counter % 10) as f32 - 5.0).abs() / 5.0;
-        self.leds = (0..LED_COUNT)
-            .map(|_| LED::from(winner).brightness(bright))
-            .collect();
+        let winner: LED = winner.into();
+        self.leds = (0..LED_COUNT).map(|_| winner.brightness(bright)).collect();
+        self.leds[counter % LED_COUNT] = LED::black();
+        self.leds[(counter + LED_COUNT / 2) % LED_COUNT] = LED::black();
+    }
+
+    /// Every player gets an arc proportional to their score, the best first,
+    /// with the same moving gaps as `game_winner`.
+    pub fn game_ranking(&mut self, mut ranking: Vec<(PlayColor, usize)>, counter: usize) {
+        ranking.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+        let total: usize = ranking.iter().map(|(_, score)| score).sum();
+        self.leds = (0..LED_COUNT).map(|_| LED::black()).collect();
+        let mut start = 0;
+        for (i, (color, score)) in ranking.iter().enumerate() {
+            let width = (LED_COUNT * score)
+                .checked_div(total)
+                .unwrap_or(LED_COUNT / ranking.len());
+            let end = if i + 1 == ranking.len() && total > 0 {
+                LED_COUNT
+            } else {
+                start + width
+            };
+            (start..end).for_each(|j| self.leds[j] = (*color).into());
+            start = end;
+        }
         self.leds[counter % LED_COUNT] = LED::black();
         self.leds[(counter + LED_COUNT / 2) % LED_COUNT] = LED::black();
     }
 
     pub fn game_signup(&mut self, players: Vec<PlayColor>, counter: usize) {
         (0..counter).for_each(|i| self.leds[i] = LED::white());
-        let player_width = LED_COUNT / 6;
+        let player_width = LED_COUNT / MAX_PLAYERS;
         for (i, p) in players.iter().enumerate() {
             for j in 0..player_width {
                 self.leds[i * player_width + j] = (*p).into();
@@ -99,8 +182,34 @@ impl Display {
         (counter..LED_COUNT).for_each(|i| self.leds[i] = LED::black());
     }
 
+    /// Every team gets its part of the circle, dimly lit in the team color,
+    /// with its players at the beginning of it.
+    pub fn game_signup_teams(
+        &mut self,
+        teams: usize,
+        players: Vec<(PlayColor, Team)>,
+        counter: usize,
+    ) {
+        (0..counter).for_each(|i| self.leds[i] = LED::white());
+        let team_width = LED_COUNT / teams;
+        let player_width = team_width / MAX_PLAYERS;
+        for (i, team) in Team::all(teams).iter().enumerate() {
+            for j in 0..team_width {
+                self.leds[i * team_width + j] = LED::from(*team).brightness(0.3);
+            }
+            let members = players.iter().filter(|(_, t)| t == team);
+            for (k, (p, _)) in members.enumerate() {
+                for j in 0..player_width {
+                    self.leds[i * team_width + k * player_width + j] = (*p).into();
+                }
+            }
+        }
+        (counter..LED_COUNT).for_each(|i| self.leds[i] = LED::black());
+    }
+
     pub fn tick(&mut self) {
         self.counter += 1;
+        self.layers.values_mut().for_each(|canvas| canvas.clear());
     }
 
     pub fn reset(&mut self) {
@@ -109,12 +218,17 @@ impl Display {
     }
 
     pub fn shine(&mut self, phi: f32) {
-        for led in &mut self.leds {
-            if led.is_black() {
-                *led = LED::from_hex("888888");
-            }
-            *led = led.brightness((phi * TAU).sin() + 1.);
-        }
+        let shine = LED::from_hex("888888").brightness((phi * TAU).sin() + 1.);
+        self.set_blend(Layer::Overlay, Blend::Max);
+        let canvas = self.canvas(Layer::Overlay);
+        (0..LED_COUNT).for_each(|i| canvas.draw(i, shine, Blend::Replace));
+    }
+
+    /// Multiplies everything below with `tint`, so only its colors shine through.
+    pub fn tint(&mut self, tint: LED) {
+        self.set_blend(Layer::Filter, Blend::Multiply);
+        let canvas = self.canvas(Layer::Filter);
+        (0..LED_COUNT).for_each(|i| canvas.draw(i, tint, Blend::Replace));
     }
 
     fn neighbors(&self, i: usize) -> (LED, LED) {
@@ -137,50 +251,234 @@ impl Display {
     }
 }
 
+/// How the frames of two games are mixed while switching from one to the other.
+#[derive(EnumString, Debug, Clone, Copy, PartialEq)]
+#[strum(ascii_case_insensitive)]
+pub enum Transition {
+    Crossfade,
+    Wipe,
+    FadeBlack,
+}
+
+impl Transition {
+    /// Mixes the frames of the old and the new game, `progress` going from 0 (old) to 1 (new).
+    pub fn mix(&self, progress: f32, old: &str, new: &str) -> String {
+        let progress = progress.clamp(0., 1.);
+        Self::leds(old)
+            .into_iter()
+            .zip(Self::leds(new))
+            .enumerate()
+            .map(|(i, (old, new))| match self {
+                Transition::Crossfade => old.blend(new, Blend::Alpha(progress)),
+                Transition::Wipe => {
+                    let cover = (progress * LED_COUNT as f32 - i as f32).clamp(0., 1.);
+                    old.blend(new, Blend::Alpha(cover))
+                }
+                Transition::FadeBlack => {
+                    if progress < 0.5 {
+                        old.brightness(1. - progress * 2.)
+                    } else {
+                        new.brightness(progress * 2. - 1.)
+                    }
+                }
+            })
+            .map(|l| l.to_string())
+            .collect::<Vec<String>>()
+            .join("")
+    }
+
+    fn leds(circle: &str) -> Vec<LED> {
+        (0..LED_COUNT)
+            .map(|i| {
+                circle
+                    .get(i * 6..i * 6 + 6)
+                    .map_or(LED::black(), LED::from_hex)
+            })
+            .collect()
+    }
+}
+
+/// The LEDs of one layer, where `None` lets the layers below shine through.
+#[derive(Debug)]
+pub struct Canvas {
+    blend: Blend,
+    leds: Vec<Option<LED>>,
+}
+
+impl Canvas {
+    fn new(blend: Blend) -> Self {
+        Self {
+            blend,
+            leds: vec![None; LED_COUNT],
+        }
+    }
+
+    fn clear(&mut self) {
+        self.leds.iter_mut().for_each(|led| *led = None);
+    }
+
+    /// Draws a LED on this canvas, blending it with what has already been drawn
+    /// in the same frame.
+    pub fn draw(&mut self, i: usize, led: LED, blend: Blend) {
+        let i = i % LED_COUNT;
+        self.leds[i] = Some(match self.leds[i] {
+            Some(below) => below.blend(led, blend),
+            None => led,
+        });
+    }
+
+    /// Draws an arc of `width` LEDs centered on the fractional LED position `center`.
+    /// LEDs which are only partially covered get a proportional part of the
+    /// intensity, so slow movements don't jump from one LED to the next.
+    pub fn draw_arc(&mut self, center: f32, width: f32, led: LED, blend: Blend) {
+        let (start, end) = (center - width / 2., center + width / 2.);
+        for i in (start + 0.5).floor() as i32..=(end + 0.5).floor() as i32 {
+            let cover = (end.min(i as f32 + 0.5) - start.max(i as f32 - 0.5)).clamp(0., 1.);
+            if cover > 0. {
+                let i = i.rem_euclid(LED_COUNT as i32) as usize;
+                self.draw(i, led.brightness(cover), blend);
+            }
+        }
+    }
+}
+
+/// Things to draw on a canvas. The positions are fractional LEDs.
 pub enum Blob {
     Player(Player),
-    Obstacle(Position),
-    Bonus(Position),
-    Drop(Position, PlayColor, f32),
+    Obstacle(f32, f32, ObstacleKind),
+    Pickup(f32, Pickup),
+    Drop(f32, PlayColor, f32),
+    Projectile(f32, PlayColor, f32),
+    Target(f32, f32),
+    /// The center and width of the sector of a player, with their lives left.
+    Sector(f32, f32, PlayColor, usize),
 }
 
 impl Blob {
-    pub fn draw(&self, counter: usize, leds: &mut Vec<LED>) {
+    pub fn draw(&self, counter: usize, canvas: &mut Canvas) {
         match self {
-            Blob::Player(player) => Self::draw_player(player, counter, leds),
-            Blob::Obstacle(pos) => leds[pos.0] = LED::from_hex("ff2222"),
-            Blob::Bonus(pos) => leds[pos.0] = LED::from_hex("22ff22"),
-            Blob::Drop(pos, play_color, speed) => {
-                for i in 0..(3 + speed.abs() as usize) {
-                    leds[(pos.0 + i) % LED_COUNT] = (*play_color).into();
-                    leds[(pos.0 + LED_COUNT - i) % LED_COUNT] = (*play_color).into();
+            Blob::Player(player) => Self::draw_player(player, counter, canvas),
+            Blob::Obstacle(pos, width, kind) => {
+                Self::draw_obstacle(*pos, *width, *kind, counter, canvas)
+            }
+            Blob::Pickup(pos, pickup) => Self::draw_pickup(*pos, *pickup, counter, canvas),
+            Blob::Projectile(pos, play_color, speed) => {
+                // A fading tail shows where the projectile is going.
+                let led = LED::from(*play_color);
+                for i in 0..PROJECTILE_TAIL {
+                    let behind = pos - speed.signum() * i as f32;
+                    let fade = 1. - i as f32 / PROJECTILE_TAIL as f32;
+                    canvas.draw_arc(behind, 1., led.brightness(fade), Blend::Max);
+                }
+            }
+            Blob::Target(pos, width) => {
+                let pulse = (counter as f32 / FREQUENCY as f32 * TAU).sin() * 0.1 + 0.3;
+                let white = LED::white();
+                canvas.draw_arc(*pos, *width, white.brightness(pulse), Blend::Add);
+                canvas.draw_arc(*pos - width / 2., 1., white, Blend::Add);
+                canvas.draw_arc(*pos + width / 2., 1., white, Blend::Add);
+            }
+            Blob::Drop(pos, play_color, width) => {
+                canvas.draw_arc(*pos, *width, (*play_color).into(), Blend::Max);
+            }
+            Blob::Sector(pos, width, play_color, lifes) => {
+                // A dim sector, with one bright dot per life at its beginning.
+                let led = LED::from(*play_color);
+                canvas.draw_arc(*pos, *width, led.brightness(0.1), Blend::Max);
+                for i in 0..*lifes {
+                    let dot = pos - width / 2. + 1.5 + 2. * i as f32;
+                    canvas.draw_arc(dot, 1., led.brightness(0.6), Blend::Max);
                 }
             }
         }
     }
 
-    fn draw_player(player: &Player, counter: usize, leds: &mut Vec<LED>) {
-        let mut tmp: Vec<LED> = (0..LED_COUNT).map(|_| LED::black()).collect();
-        if player.jump == 0 || (counter % BLINK_JUMP < BLINK_JUMP / 2) {
-            tmp[player.pos.0].xor(player.color.into());
+    /// The obstacles are all reddish, with a pattern showing how they move.
+    fn draw_obstacle(
+        pos: f32,
+        width: f32,
+        kind: ObstacleKind,
+        counter: usize,
+        canvas: &mut Canvas,
+    ) {
+        match kind {
+            ObstacleKind::Normal => {
+                canvas.draw_arc(pos, width, LED::from_hex("ff2222"), Blend::Add)
+            }
+            ObstacleKind::Dart => canvas.draw_arc(pos, width, LED::from_hex("ff0044"), Blend::Add),
+            ObstacleKind::Wall => {
+                canvas.draw_arc(pos, width, LED::from_hex("881111"), Blend::Add);
+                canvas.draw_arc(pos, 1., LED::from_hex("ff2222"), Blend::Add);
+            }
+            ObstacleKind::Zigzag => {
+                let led = LED::from_hex("ff2266");
+                let blink = counter % BLINK_PICKUP < BLINK_PICKUP / 2;
+                canvas.draw_arc(
+                    pos,
+                    width,
+                    led.brightness(if blink { 1. } else { 0.5 }),
+                    Blend::Add,
+                );
+            }
+            ObstacleKind::Splitter => {
+                let led = LED::from_hex("ff2222");
+                canvas.draw_arc(pos - 0.5, width, led.brightness(0.7), Blend::Add);
+                canvas.draw_arc(pos + 0.5, width, led.brightness(0.7), Blend::Add);
+            }
+        }
+    }
+
+    /// Every pickup has its own pattern, so they can be told apart besides the color.
+    fn draw_pickup(pos: f32, pickup: Pickup, counter: usize, canvas: &mut Canvas) {
+        let led = LED::from(pickup);
+        let blink = counter % BLINK_PICKUP < BLINK_PICKUP / 2;
+        match pickup {
+            Pickup::Life => canvas.draw_arc(pos, 1., led, Blend::Add),
+            Pickup::Shield => canvas.draw_arc(pos, 3., led, Blend::Add),
+            Pickup::Speed if blink => canvas.draw_arc(pos, 1., led, Blend::Add),
+            Pickup::Speed => {}
+            Pickup::Invert => {
+                let offset = if blink { -1. } else { 1. };
+                canvas.draw_arc(pos + offset, 1., led, Blend::Add);
+            }
+            Pickup::Freeze => {
+                let pulse = (counter as f32 / FREQUENCY as f32 * TAU).sin() * 0.3 + 0.7;
+                canvas.draw_arc(pos, 3., led.brightness(pulse), Blend::Add);
+            }
+            Pickup::JumpRefill => {
+                canvas.draw_arc(pos - 1., 1., led, Blend::Add);
+                canvas.draw_arc(pos + 1., 1., led, Blend::Add);
+            }
         }
+    }
+
+    fn draw_player(player: &Player, counter: usize, canvas: &mut Canvas) {
+        let pos = player.pos_smooth();
         if player.jump == 0
             && (player.jump_recover == 0 || (counter % BLINK_RECOVER < BLINK_RECOVER / 2))
         {
-            for dist in 1..player.lifes * 2 {
-                tmp[player.pos.add(dist as i32).0].xor(LED::from(player.color).brightness(0.1));
-                tmp[player.pos.sub(dist as i32).0].xor(LED::from(player.color).brightness(0.1));
-            }
+            let halo = LED::from(player.color).brightness(0.1);
+            let width = (player.lifes * 4) as f32 - 1.;
+            canvas.draw_arc(pos, width, halo, Blend::Add);
         }
-
-        for (i, l) in tmp.into_iter().enumerate() {
-            if !l.is_black() {
-                leds[i] = l;
-            }
+        if player.has(Pickup::Shield) {
+            let shield = LED::from(Pickup::Shield).brightness(0.3);
+            let width = (player.lifes * 4) as f32 + 3.;
+            canvas.draw_arc(pos, width, shield, Blend::Add);
+        }
+        if player.jump == 0 || (counter % BLINK_JUMP < BLINK_JUMP / 2) {
+            let led = if player.has(Pickup::Invert) && counter % BLINK_PICKUP < BLINK_PICKUP / 2 {
+                Pickup::Invert.into()
+            } else {
+                player.color.into()
+            };
+            // Xor keeps the player visible on top of its own halo.
+            canvas.draw_arc(pos, 1., led, Blend::Xor);
         }
     }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Copy)]
 pub struct LED {
     red: u8,
@@ -241,11 +539,7 @@ impl LED {
         l
     }
 
-    pub fn is_black(&self) -> bool {
-        self.red == 0 && self.green == 0 && self.blue == 0
-    }
-
-    pub fn brightness(&mut self, delta: f32) -> LED {
+    pub fn brightness(&self, delta: f32) -> LED {
         Self {
             red: Self::calc_bright(self.red, delta),
             green: Self::calc_bright(self.green, delta),
@@ -269,14 +563,25 @@ impl LED {
         }
     }
 
-    pub fn to_string(&self) -> String {
-        format!("{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
-    }
-
-    pub fn xor(&mut self, other: LED) {
-        self.red ^= other.red;
-        self.green ^= other.green;
-        self.blue ^= other.blue;
+    pub fn blend(&self, top: LED, blend: Blend) -> LED {
+        let channel = |below: u8, top: u8| -> u8 {
+            match blend {
+                Blend::Replace => top,
+                Blend::Add => below.saturating_add(top),
+                Blend::Multiply => (below as usize * top as usize / 255) as u8,
+                Blend::Alpha(alpha) => {
+                    let alpha = alpha.clamp(0., 1.);
+                    (below as f32 * (1. - alpha) + top as f32 * alpha).round() as u8
+                }
+                Blend::Xor => below ^ top,
+                Blend::Max => below.max(top),
+            }
+        };
+        LED {
+            red: channel(self.red, top.red),
+            green: channel(self.green, top.green),
+            blue: channel(self.blue, top.blue),
+        }
     }
 
     fn calc_bright(c: u8, delta: f32) -> u8 {
@@ -291,8 +596,26 @@ impl LED {
     }
 }
 
+impl fmt::Display for LED {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+}
+
 impl From<PlayColor> for LED {
     fn from(value: PlayColor) -> Self {
         LED::from_hex(&value.to_hex())
     }
 }
+
+impl From<Pickup> for LED {
+    fn from(value: Pickup) -> Self {
+        LED::from_hex(&value.to_hex())
+    }
+}
+
+impl From<Team> for LED {
+    fn from(value: Team) -> Self {
+        LED::from_hex(&value.to_hex())
+    }
+}
@@ -1,151 +1,939 @@
-use std::f32::consts::{PI, TAU};
+use std::{
+    collections::HashMap,
+    f32::consts::{PI, TAU},
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
+use async_std::task::sleep;
 use dioxus::prelude::*;
 use serde::{Deserialize, Serialize};
+use strum_macros::EnumString;
 
 use crate::{
-    common::{PlayColor, LED_COUNT},
-    display::{Blob, Display},
-    games::snake_board::Position,
+    common::{Game, Limited, PlayColor, FREQUENCY, LED_COUNT},
+    display::{Blob, Display, Layer},
     server, Route,
 };
 
+/// Shows the free play, or the screens of the competition once a player joined it.
 #[component]
 pub fn Drop() -> Element {
+    let mut state = use_signal(DropStatus::default);
+    let player: Signal<Option<PlayColor>> = use_signal(|| None);
+    let session: Signal<Option<String>> = use_signal(|| None);
+    let tilting: Signal<Option<String>> = use_signal(|| None);
+
+    use_future(move || async move {
+        loop {
+            if let Some(tilter) = tilting() {
+                let tilt = document::eval("return window.dropTilt ?? null;")
+                    .join::<Option<f32>>()
+                    .await;
+                if let Ok(Some(angle)) = tilt {
+                    if let Err(e) = drop_tilt(tilter, angle).await {
+                        tracing::error!("While sending the tilt: {e:?}");
+                    }
+                }
+            }
+            sleep(Duration::from_millis(TILT_INTERVAL_MS)).await;
+        }
+    });
+
+    use_future(move || async move {
+        loop {
+            match drop_state().await {
+                Ok(s) => state.set(s),
+                Err(e) => tracing::error!("While getting the drop state: {e:?}"),
+            }
+            sleep(Duration::from_millis(500)).await;
+        }
+    });
+
+    let DropStatus {
+        game,
+        scores,
+        seconds,
+    } = state();
     rsx! {
         div {
-            id: "drop-grid",
-
-            for color in PlayColor::all(){
-                button {onclick: move |_| async move {
-                    if let Err(e) = drop_color(Side::Left, color).await{
-                        tracing::error!("{e:?}");
+            match game {
+                DropGame::Toy => rsx!{Toy {player, session, tilting}},
+                DropGame::Signup(players) => if player().is_some() {
+                    rsx!{
+                        div {
+                            class: "centered-div",
+                            "En attente d'autres joueurs - {join_colors(&players)}"
+                            br{}
+                            if players.len() > 1 {
+                                "La partie commence dans {seconds} s"
+                            }
+                        }
                     }
+                } else {
+                    rsx!{JoinRound {players, player, session}}
                 },
-                    class:"color-block", style:"background-color: #{color.to_hex_pastel()};",
-                    "{color.to_string()}"
-                }
-                div{
-                    class: "color-block",
+                DropGame::Round(_) => match (player(), session()) {
+                    (Some(player), Some(session)) => rsx!{Launch {player, session, scores, seconds}},
+                    _ => rsx!{
+                        div {
+                            class: "centered-div",
+                            "Une partie est en cours, encore {seconds} s"
+                            Scoreboard {scores}
+                        }
+                    },
+                },
+                DropGame::Winner(winners) => rsx!{RoundWinner {winners, player: player(), scores}},
+            }
+        }
+    }
+}
+
+fn join_colors(colors: &[PlayColor]) -> String {
+    colors
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<String>>()
+        .join(" : ")
+}
+
+/// Everybody can throw every color, and start a competition with the chosen color.
+#[component]
+fn Toy(
+    mut player: Signal<Option<PlayColor>>,
+    mut session: Signal<Option<String>>,
+    mut tilting: Signal<Option<String>>,
+) -> Element {
+    let mut cooldown = use_signal(|| 0u64);
+    let mut color = use_signal(|| PlayColor::RED);
 
-                    if color == PlayColor::Red {
-                        Link {to: Route::Reset{}, style: "text-align: center; width: 100%;", "Home"}
+    use_effect(move || {
+        player.set(None);
+        session.set(None);
+    });
+
+    let throw = move |throw: Throw| async move {
+        match drop_color(throw, color()).await {
+            Ok(Limited::Accepted(_)) => {}
+            Ok(Limited::Cooldown(ms)) => {
+                cooldown.set(ms);
+                sleep(Duration::from_millis(ms)).await;
+                cooldown.set(0);
+            }
+            Err(e) => tracing::error!("{e:?}"),
+        }
+    };
+
+    rsx! {
+        div {
+            class: "drop-page",
+            div {
+                class: "drop-colors",
+                for c in PlayColor::basic() {
+                    button {
+                        class: if c == color() { "color-block drop-chosen" } else { "color-block" },
+                        style: "background-color: #{c.to_hex_pastel()};",
+                        onclick: move |_| color.set(c),
+                        "{c}"
                     }
                 }
-                button {onclick: move |_| async move {
-                    if let Err(e) = drop_color(Side::Right, color).await{
-                        tracing::error!("{e:?}");
-                    }
-                },
-                    class:"color-block", style:"background-color: #{color.to_hex_pastel()};",
-                    "{color.to_string()}"
+            }
+            Dial {color: color(), cooldown: cooldown(), onthrow: throw}
+            div {
+                class: "drop-colors",
+                button {
+                    class: "drop-join",
+                    style: "border-color: #{color().to_hex()};",
+                    onclick: move |_| async move {join(color(), player, session).await},
+                    "Défier"
+                }
+                button {
+                    class: "drop-join",
+                    onclick: move |_| async move {
+                        if tilting().is_some() {
+                            tilting.set(None);
+                            return;
+                        }
+                        document::eval(include_str!("../../tilt.js"));
+                        match drop_control().await {
+                            Ok(tilter) => tilting.set(Some(tilter)),
+                            Err(e) => tracing::error!("While taking the control: {e:?}"),
+                        }
+                    },
+                    if tilting().is_some() { "Arrêter de pencher" } else { "Pencher" }
+                }
+                Link {to: Route::Reset{}, style: "text-align: center; width: 100%;", "Home"}
+            }
+        }
+    }
+}
+
+/// The ring as a circle: touching it chooses where the drop starts,
+/// and swiping along it gives the drop its speed and direction.
+/// A tap without swiping lets the drop fall from there.
+#[component]
+fn Dial(color: PlayColor, cooldown: u64, onthrow: EventHandler<Throw>) -> Element {
+    let mut dial: Signal<Option<Rc<MountedData>>> = use_signal(|| None);
+    let mut start: Signal<Option<(f64, f64)>> = use_signal(|| None);
+
+    rsx! {
+        div {
+            class: if cooldown > 0 { "drop-dial cooldown" } else { "drop-dial" },
+            style: "border-color: #{color.to_hex()};",
+            onmounted: move |e| dial.set(Some(e.data())),
+            onpointerdown: move |e| {
+                let pos = e.client_coordinates();
+                start.set(Some((pos.x, pos.y)));
+            },
+            onpointerup: move |e| async move {
+                let (Some((x0, y0)), Some(dial)) = (start.take(), dial()) else {
+                    return;
+                };
+                let Ok(rect) = dial.get_client_rect().await else {
+                    return;
+                };
+                let (center, radius) = (rect.center(), rect.width() / 2.);
+                // The angle starts at the top and goes clockwise, like the LEDs.
+                let angle = (x0 - center.x).atan2(center.y - y0);
+                let end = e.client_coordinates();
+                let swipe = (end.x - x0) * angle.cos() + (end.y - y0) * angle.sin();
+                onthrow.call(Throw {
+                    angle: angle as f32,
+                    speed: (swipe / radius) as f32 * SWIPE_SPEED,
+                });
+            },
+            if cooldown > 0 { "{cooldown} ms" } else { "Touche et glisse" }
+        }
+    }
+}
+
+async fn join(
+    color: PlayColor,
+    mut player: Signal<Option<PlayColor>>,
+    mut session: Signal<Option<String>>,
+) {
+    match drop_join(color).await {
+        Ok(Limited::Accepted(Some(s))) => {
+            player.set(Some(color));
+            session.set(Some(s));
+        }
+        Ok(Limited::Accepted(None)) => tracing::warn!("Couldn't join with {color}"),
+        Ok(Limited::Cooldown(ms)) => tracing::warn!("Joining too fast, wait {ms}ms"),
+        Err(e) => tracing::error!("While joining: {e:?}"),
+    }
+}
+
+#[component]
+fn JoinRound(
+    players: Vec<PlayColor>,
+    player: Signal<Option<PlayColor>>,
+    session: Signal<Option<String>>,
+) -> Element {
+    rsx! {
+        div {
+            class: "centered-div",
+            "Une partie se prépare - {join_colors(&players)}"
+            br{}
+            for color in PlayColor::basic().into_iter().filter(|c| !players.contains(c)) {
+                button {
+                    class: "drop-join",
+                    style: "background-color: #{color.to_hex_pastel()};",
+                    onclick: move |_| async move {join(color, player, session).await},
+                    "{color}"
                 }
             }
         }
     }
 }
 
+/// During a round, the player can only throw their own color.
+#[component]
+fn Launch(
+    player: PlayColor,
+    session: String,
+    scores: Vec<(PlayColor, usize)>,
+    seconds: usize,
+) -> Element {
+    let session = use_signal(|| session);
+    let mut cooldown = use_signal(|| 0u64);
+
+    let launch = move |throw: Throw| async move {
+        match drop_launch(session(), throw).await {
+            Ok(Limited::Accepted(_)) => {}
+            Ok(Limited::Cooldown(ms)) => {
+                cooldown.set(ms);
+                sleep(Duration::from_millis(ms)).await;
+                cooldown.set(0);
+            }
+            Err(e) => tracing::error!("While launching: {e:?}"),
+        }
+    };
+
+    rsx! {
+        div {
+            class: "drop-page",
+            div {
+                class: "drop-scores",
+                "Encore {seconds} s"
+                Scoreboard {scores}
+            }
+            Dial {color: player, cooldown: cooldown(), onthrow: launch}
+        }
+    }
+}
+
+#[component]
+fn Scoreboard(scores: Vec<(PlayColor, usize)>) -> Element {
+    let mut scores = scores;
+    scores.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+    rsx! {
+        div {
+            for (color, score) in scores {
+                div {
+                    style: "color: #{color.to_hex()};",
+                    "{color}: {score}"
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn RoundWinner(
+    winners: Vec<PlayColor>,
+    player: Option<PlayColor>,
+    scores: Vec<(PlayColor, usize)>,
+) -> Element {
+    let won = player.is_some_and(|p| winners.contains(&p));
+    use_effect(move || {
+        if won {
+            document::eval(include_str!("../../fireworks.js"));
+        }
+    });
+
+    rsx! {
+        div {
+            class: "centered-div",
+
+            if winners.len() == 1 {
+                "{join_colors(&winners)} a gagné!"
+            } else {
+                "Égalité entre {join_colors(&winners)}!"
+            }
+            br{}
+            br{}
+            canvas {display: "block", id: "fireworks"}
+            Scoreboard {scores}
+            if player.is_some() {
+                if won {
+                    "Toutes nos félicitations!"
+                } else {
+                    "Bonne chance la prochaine fois!"
+                }
+            }
+        }
+    }
+}
+
+/// The width of a drop of mass 1, in LEDs.
+const DROP_WIDTH: f32 = 5.;
+#[cfg(debug_assertions)]
+const COUNTDOWN_SIGNUP: usize = 2;
+#[cfg(not(debug_assertions))]
+const COUNTDOWN_SIGNUP: usize = LED_COUNT;
+const ROUND_DURATION: usize = 60 * FREQUENCY;
+const COUNTDOWN_WINNER: usize = 4 * FREQUENCY;
+const TARGET_COUNT: usize = 2;
+/// The width of a target, in LEDs.
+const TARGET_WIDTH: f32 = 15.;
+
+const TILT_INTERVAL_MS: u64 = 200;
+/// In radians per tick, a bit more than a drop falling from the top gets.
+const MAX_THROW_SPEED: f32 = 0.15;
+/// The speed of a swipe as long as the radius of the dial.
+const SWIPE_SPEED: f32 = 0.1;
+/// Tilts older than this are forgotten, e.g. when the phone stopped streaming.
+const TILT_TIMEOUT: Duration = Duration::from_secs(1);
+/// How fast the gravity follows the tilt, in radians per tick.
+const TILT_SPEED: f32 = 0.05;
+
+/// How the drops behave when they touch.
+/// With an `elasticity` of 1 they bounce back without losing speed, with 0 they stick together.
+/// If `mix` is set, drops of different colors merge into one drop of the mixed color.
+/// `tilt` chooses which phones turn the gravity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DropPhysics {
+    pub elasticity: f32,
+    pub mix: bool,
+    pub tilt: Tilt,
+}
+
+impl Default for DropPhysics {
+    fn default() -> Self {
+        Self {
+            elasticity: 0.9,
+            mix: false,
+            tilt: Tilt::Average,
+        }
+    }
+}
+
+/// Without tilting phones, the gravity points to the bottom of the ring.
+#[derive(EnumString, Debug, Clone, Copy, PartialEq)]
+#[strum(ascii_case_insensitive)]
+pub enum Tilt {
+    Off,
+    /// Only the last phone which took the control tilts the ring.
+    Controller,
+    /// The tilts of all phones are averaged.
+    Average,
+}
+
+/// Without a competition, the drops are a toy for everybody.
+/// In a competition, every player owns a color and scores when one of their drops
+/// turns around or comes to rest in a target.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum DropGame {
+    Toy,
+    Signup(Vec<PlayColor>),
+    Round(Vec<PlayColor>),
+    Winner(Vec<PlayColor>),
+}
+
+/// What the phones poll, with the seconds left in the signup or the round.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DropStatus {
+    pub game: DropGame,
+    pub scores: Vec<(PlayColor, usize)>,
+    pub seconds: usize,
+}
+
+impl Default for DropStatus {
+    fn default() -> Self {
+        Self {
+            game: DropGame::Toy,
+            scores: vec![],
+            seconds: 0,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct PlatformDrop {
     display: Display,
     drops: Vec<DropBlob>,
+    physics: DropPhysics,
+    last_input: Instant,
+    game: DropGame,
+    countdown: usize,
+    sessions: HashMap<String, PlayColor>,
+    scores: Vec<(PlayColor, usize)>,
+    /// The angles of the centers of the targets.
+    targets: Vec<f32>,
+    /// The angle the drops fall to.
+    gravity: f32,
+    /// The latest angles of the tilting phones, by the id `drop_control` gave them.
+    tilts: HashMap<String, (f32, Instant)>,
+    controller: Option<String>,
 }
 
 impl PlatformDrop {
-    pub fn new() -> Self {
+    pub fn new(physics: DropPhysics) -> Self {
         Self {
             display: Display::new(),
             drops: vec![],
+            physics,
+            last_input: Instant::now(),
+            game: DropGame::Toy,
+            countdown: 0,
+            sessions: HashMap::new(),
+            scores: vec![],
+            targets: vec![],
+            gravity: PI,
+            tilts: HashMap::new(),
+            controller: None,
         }
     }
 
+    /// Nobody is in a competition.
+    pub fn is_idle(&self) -> bool {
+        self.game == DropGame::Toy
+    }
+
+    pub fn has_session(&self, session: &str) -> bool {
+        self.sessions.contains_key(session)
+    }
+
     pub fn get_circle(&self) -> String {
         self.display.get_circle()
     }
 
+    pub fn last_input(&self) -> Instant {
+        self.last_input
+    }
+
     pub fn message(&mut self, msg: MessagesDrop) -> Option<AnswerDrop> {
+        if !matches!(
+            msg,
+            MessagesDrop::Tick | MessagesDrop::GetState | MessagesDrop::Tilt(_, _)
+        ) {
+            self.last_input = Instant::now();
+        }
         match msg {
-            MessagesDrop::Tick => {
-                self.display.clear();
-                let drops = self
-                    .drops
-                    .iter_mut()
-                    .map(|drop| {
-                        drop.tick();
-                        drop.display()
-                    })
-                    .collect();
-                self.display.draw_blobs(drops);
-            }
-            MessagesDrop::DropColor(side, play_color) => {
-                self.drops.push(DropBlob::new(side, play_color));
-                if self.drops.len() > 10 {
-                    self.drops.remove(0);
+            MessagesDrop::Tick => self.tick(),
+            MessagesDrop::DropColor(throw, play_color) => {
+                if self.game == DropGame::Toy {
+                    self.add_drop(DropBlob::new(throw, play_color, None));
+                }
+            }
+            MessagesDrop::Join(play_color) => {
+                return Some(AnswerDrop::Joined(self.join(play_color)));
+            }
+            MessagesDrop::Launch(session, throw) => {
+                if let (DropGame::Round(_), Some(c)) = (&self.game, self.sessions.get(&session)) {
+                    self.add_drop(DropBlob::new(throw, *c, Some(*c)));
                 }
             }
+            // A single invalid angle would make the gravity NaN for everybody.
+            MessagesDrop::Tilt(tilter, angle) if angle.is_finite() => {
+                self.tilts.insert(tilter, (angle, Instant::now()));
+            }
+            MessagesDrop::Tilt(_, _) => {}
+            MessagesDrop::TakeControl(tilter) => self.controller = Some(tilter),
+            MessagesDrop::GetState => {
+                return Some(AnswerDrop::State(DropStatus {
+                    game: self.game.clone(),
+                    scores: self.scores.clone(),
+                    seconds: self.countdown.div_ceil(FREQUENCY),
+                }))
+            }
         }
         None
     }
-}
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub enum Side {
-    Left,
-    Right,
-}
+    fn add_drop(&mut self, drop: DropBlob) {
+        self.drops.push(drop);
+        if self.drops.len() > 10 {
+            self.drops.remove(0);
+        }
+    }
+
+    /// Turns the gravity towards the tilt of the phones, a bit on every tick
+    /// so the drops slosh around instead of jumping.
+    fn follow_tilt(&mut self) {
+        self.tilts
+            .retain(|_, (_, time)| time.elapsed() < TILT_TIMEOUT);
+        let angles: Vec<f32> = match self.physics.tilt {
+            Tilt::Off => vec![],
+            Tilt::Controller => self
+                .controller
+                .as_ref()
+                .and_then(|c| self.tilts.get(c))
+                .map(|(angle, _)| *angle)
+                .into_iter()
+                .collect(),
+            Tilt::Average => self.tilts.values().map(|(angle, _)| *angle).collect(),
+        };
+        let (x, y) = angles
+            .iter()
+            .fold((0., 0.), |(x, y), a| (x + a.sin(), y + a.cos()));
+        let down = if x.hypot(y) < 0.01 { PI } else { x.atan2(y) };
+        let diff = (down - self.gravity + PI).rem_euclid(TAU) - PI;
+        self.gravity = (self.gravity + diff.clamp(-TILT_SPEED, TILT_SPEED)).rem_euclid(TAU);
+    }
+
+    /// Every color can only be owned by one phone.
+    /// Once a second player joins, the round starts after a countdown.
+    fn join(&mut self, c: PlayColor) -> Option<String> {
+        let players = match &self.game {
+            DropGame::Toy => vec![],
+            DropGame::Signup(players) if !players.contains(&c) => players.clone(),
+            _ => return None,
+        };
+        let players = [players, vec![c]].concat();
+        if players.len() > 1 {
+            self.countdown = COUNTDOWN_SIGNUP;
+        }
+        self.scores = players.iter().map(|p| (*p, 0)).collect();
+        self.game = DropGame::Signup(players);
+        let session = format!("{:032x}", rand::random::<u128>());
+        self.sessions.insert(session.clone(), c);
+        Some(session)
+    }
 
-impl Side {
-    pub fn angle_start(&self) -> f32 {
-        if self == &Side::Left {
-            TAU - 0.01
-        } else {
-            0.01
+    fn tick(&mut self) {
+        self.display.tick();
+        self.display.clear();
+        match self.game.clone() {
+            DropGame::Signup(players) => {
+                self.display.game_signup(players, self.countdown);
+            }
+            DropGame::Winner(winners) => {
+                let winner = winners.first().copied().unwrap_or(PlayColor::RED);
+                self.display.game_winner(winner, self.countdown);
+            }
+            DropGame::Toy | DropGame::Round(_) => {
+                self.follow_tilt();
+                let down = self.gravity;
+                self.drops.iter_mut().for_each(|drop| drop.tick(down));
+                self.collide();
+                if matches!(self.game, DropGame::Round(_)) {
+                    self.score();
+                }
+                // The targets blink during the last seconds of the round.
+                let blink =
+                    self.countdown < 5 * FREQUENCY && self.countdown % FREQUENCY < FREQUENCY / 2;
+                let mut blobs: Vec<Blob> = if blink {
+                    vec![]
+                } else {
+                    self.targets
+                        .iter()
+                        .map(|t| Blob::Target(Self::to_led(*t), TARGET_WIDTH))
+                        .collect()
+                };
+                blobs.extend(self.drops.iter().map(|drop| drop.display()));
+                self.display.draw_blobs(Layer::Entities, blobs);
+            }
+        }
+        self.display.flatten();
+
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            if self.countdown == 0 {
+                self.game = match self.game.clone() {
+                    DropGame::Signup(players) => {
+                        self.drops.clear();
+                        self.targets = (0..TARGET_COUNT).map(|_| Self::random_target()).collect();
+                        self.countdown = ROUND_DURATION;
+                        DropGame::Round(players)
+                    }
+                    DropGame::Round(_) => {
+                        let best = self.scores.iter().map(|(_, s)| *s).max().unwrap_or(0);
+                        self.countdown = COUNTDOWN_WINNER;
+                        DropGame::Winner(
+                            self.scores
+                                .iter()
+                                .filter(|(_, s)| *s == best)
+                                .map(|(c, _)| *c)
+                                .collect(),
+                        )
+                    }
+                    _ => {
+                        self.drops.clear();
+                        self.targets.clear();
+                        self.sessions.clear();
+                        self.scores.clear();
+                        DropGame::Toy
+                    }
+                }
+            }
+        }
+    }
+
+    /// A drop of a player scores when it turns around in a target, which also
+    /// happens when it rests in there, or when it swings through the middle of a target.
+    /// The drop is then removed, and the target moves somewhere else.
+    fn score(&mut self) {
+        let mut i = 0;
+        while i < self.drops.len() {
+            let drop = &self.drops[i];
+            let target = self.targets.iter().position(|t| {
+                let dist = (drop.angle - t + PI).rem_euclid(TAU) - PI;
+                let inside = dist.abs() < TARGET_WIDTH / 2. / LED_COUNT as f32 * TAU;
+                (inside && drop.turned()) || drop.crossed(*t)
+            });
+            match (drop.owner, target) {
+                (Some(owner), Some(t)) => {
+                    if let Some((_, score)) = self.scores.iter_mut().find(|(c, _)| c == &owner) {
+                        *score += 1;
+                    }
+                    self.targets[t] = Self::random_target();
+                    self.drops.remove(i);
+                }
+                _ => i += 1,
+            }
+        }
+    }
+
+    /// The targets are in the lower half, where the drops can turn around or rest.
+    fn random_target() -> f32 {
+        PI / 2. + rand::random::<f32>() * PI
+    }
+
+    fn to_led(angle: f32) -> f32 {
+        (LED_COUNT as f32 * angle / 2. / PI).rem_euclid(LED_COUNT as f32)
+    }
+
+    /// Lets every pair of touching drops bounce off each other, or merge them.
+    fn collide(&mut self) {
+        let mut i = 0;
+        while i < self.drops.len() {
+            let mut j = i + 1;
+            while j < self.drops.len() {
+                let (a, b) = (&self.drops[i], &self.drops[j]);
+                let dist = a.distance(b);
+                if dist.abs() >= a.radius() + b.radius() {
+                    j += 1;
+                } else if self.physics.mix && a.color != b.color {
+                    let b = self.drops.remove(j);
+                    self.drops[i].merge(b, dist);
+                } else {
+                    let (left, right) = self.drops.split_at_mut(j);
+                    left[i].bounce(&mut right[0], dist, self.physics.elasticity);
+                    j += 1;
+                }
+            }
+            i += 1;
         }
     }
 }
 
+/// Where a drop starts, in radians from the top going clockwise,
+/// and its speed in radians per tick, positive going clockwise.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Throw {
+    pub angle: f32,
+    pub speed: f32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MessagesDrop {
     Tick,
-    DropColor(Side, PlayColor),
+    DropColor(Throw, PlayColor),
+    Join(PlayColor),
+    /// Drops the color of the player owning the session.
+    Launch(String, Throw),
+    /// The angle where the drops of the tilting phone with this id fall to.
+    Tilt(String, f32),
+    /// The phone with this id becomes the controller.
+    TakeControl(String),
+    GetState,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum AnswerDrop {}
+pub enum AnswerDrop {
+    Joined(Option<String>),
+    State(DropStatus),
+}
 
+/// The mass of a drop is proportional to its size.
+/// In a competition, the drops of the players have an owner.
 #[derive(Debug)]
 struct DropBlob {
     angle: f32,
     speed: f32,
+    previous_angle: f32,
+    previous_speed: f32,
+    mass: f32,
     color: PlayColor,
+    owner: Option<PlayColor>,
 }
 
 impl DropBlob {
-    fn new(side: Side, color: PlayColor) -> Self {
+    /// The speed is limited, so a drop cannot go around the ring forever.
+    fn new(throw: Throw, color: PlayColor, owner: Option<PlayColor>) -> Self {
+        let valid = |v: f32| if v.is_finite() { v } else { 0. };
+        let speed = valid(throw.speed).clamp(-MAX_THROW_SPEED, MAX_THROW_SPEED);
+        let angle = valid(throw.angle).rem_euclid(TAU);
         Self {
-            angle: side.angle_start(),
-            speed: 0.,
+            angle,
+            speed,
+            previous_angle: angle,
+            previous_speed: speed,
+            mass: 1.,
             color,
+            owner,
         }
     }
 
-    fn tick(&mut self) {
-        self.speed += self.angle.sin() / 400.;
+    /// The drop changed its direction during the last tick.
+    fn turned(&self) -> bool {
+        self.speed * self.previous_speed < 0.
+    }
+
+    /// The path of the drop during the last tick went over the `angle`.
+    fn crossed(&self, angle: f32) -> bool {
+        let before = (self.previous_angle - angle + PI).rem_euclid(TAU) - PI;
+        let moved = (self.angle - self.previous_angle + PI).rem_euclid(TAU) - PI;
+        let after = before + moved;
+        before != after && before * after <= 0.
+    }
+
+    /// Half of the width of the drop, as an angle.
+    fn radius(&self) -> f32 {
+        DROP_WIDTH * self.mass / LED_COUNT as f32 * PI
+    }
+
+    /// The signed angle from this drop to the other one, going the shortest way.
+    fn distance(&self, other: &DropBlob) -> f32 {
+        (other.angle - self.angle + PI).rem_euclid(TAU) - PI
+    }
+
+    /// An elastic collision along the circle, reduced by the elasticity.
+    /// Overlapping drops are also pushed apart, else drops resting at the bottom would sink into each other.
+    fn bounce(&mut self, other: &mut DropBlob, dist: f32, elasticity: f32) {
+        let total = self.mass + other.mass;
+        if (self.speed - other.speed) * dist > 0. {
+            let momentum = self.mass * self.speed + other.mass * other.speed;
+            let relative = other.speed - self.speed;
+            self.speed = (momentum + other.mass * elasticity * relative) / total;
+            other.speed = (momentum - self.mass * elasticity * relative) / total;
+        }
+        let overlap = (self.radius() + other.radius() - dist.abs()) * dist.signum();
+        self.angle -= overlap * other.mass / total;
+        other.angle += overlap * self.mass / total;
+    }
+
+    /// Takes the other drop in, keeping the momentum and mixing the colors by mass.
+    fn merge(&mut self, other: DropBlob, dist: f32) {
+        let total = self.mass + other.mass;
+        let weight = other.mass / total;
+        self.angle += dist * weight;
+        self.speed = (self.mass * self.speed + other.mass * other.speed) / total;
+        self.color = self.color.mix(&other.color, weight);
+        if self.owner != other.owner {
+            self.owner = None;
+        }
+        self.mass = total;
+    }
+
+    /// The drop falls towards the angle `down`.
+    fn tick(&mut self, down: f32) {
+        self.previous_angle = self.angle;
+        self.previous_speed = self.speed;
+        self.speed -= (self.angle - down).sin() / 400.;
         self.speed *= 0.999;
         self.angle += self.speed;
     }
 
     fn display(&self) -> Blob {
         Blob::Drop(
-            Position((LED_COUNT as f32 * self.angle / 2. / PI).floor() as usize),
+            (LED_COUNT as f32 * self.angle / 2. / PI).rem_euclid(LED_COUNT as f32),
             self.color,
-            self.speed * 40.,
+            DROP_WIDTH * self.mass + 2. * (self.speed * 40.).abs(),
         )
     }
 }
 
 #[server]
-pub async fn drop_color(side: Side, color: PlayColor) -> Result<Option<AnswerDrop>, ServerFnError> {
+pub async fn drop_color(
+    throw: Throw,
+    color: PlayColor,
+) -> Result<Limited<Option<AnswerDrop>>, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if let Err(wait) = plat.rate_limit(Game::Drop, &crate::client_ip().await?) {
+        return Ok(Limited::Cooldown(wait.as_millis() as u64));
+    }
+    Ok(Limited::Accepted(
+        plat.drop_message(MessagesDrop::DropColor(throw, color)),
+    ))
+}
+
+#[server(endpoint = "drop/join")]
+async fn drop_join(color: PlayColor) -> Result<Limited<Option<String>>, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if let Err(wait) = plat.rate_limit(Game::Drop, &crate::client_ip().await?) {
+        return Ok(Limited::Cooldown(wait.as_millis() as u64));
+    }
+    match plat.drop_message(MessagesDrop::Join(color)) {
+        Some(AnswerDrop::Joined(session)) => Ok(Limited::Accepted(session)),
+        _ => Ok(Limited::Accepted(None)),
+    }
+}
+
+#[server(endpoint = "drop/launch")]
+async fn drop_launch(session: String, throw: Throw) -> Result<Limited<()>, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if let Err(wait) = plat.rate_limit_session(Game::Drop, &session) {
+        return Ok(Limited::Cooldown(wait.as_millis() as u64));
+    }
+    plat.drop_message(MessagesDrop::Launch(session, throw));
+    Ok(Limited::Accepted(()))
+}
+
+/// Outside of the drop game, the phones see the toy.
+#[server(endpoint = "drop/state")]
+async fn drop_state() -> Result<DropStatus, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    match plat.drop_message(MessagesDrop::GetState) {
+        Some(AnswerDrop::State(state)) => Ok(state),
+        _ => Ok(DropStatus::default()),
+    }
+}
+
+/// The tilt is streamed several times per second, so it is not rate limited.
+/// Tilts of ids which stopped streaming are forgotten after `TILT_TIMEOUT`.
+#[server(endpoint = "drop/tilt")]
+async fn drop_tilt(tilter: String, angle: f32) -> Result<(), ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    plat.drop_message(MessagesDrop::Tilt(tilter, angle));
+    Ok(())
+}
+
+/// Gives the page a new id to stream its tilt with, and makes it the controller.
+/// The phones can't be told apart by their address, as they might all come through the same proxy.
+#[server(endpoint = "drop/control")]
+async fn drop_control() -> Result<String, ServerFnError> {
     let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
-    Ok(plat.drop_message(MessagesDrop::DropColor(side, color)))
+    let tilter = format!("{:032x}", rand::random::<u128>());
+    plat.drop_message(MessagesDrop::TakeControl(tilter.clone()));
+    Ok(tilter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fast_drop_scores_through_target() {
+        let mut platform = PlatformDrop::new(DropPhysics::default());
+        platform.game = DropGame::Round(vec![PlayColor::RED]);
+        platform.scores = vec![(PlayColor::RED, 0)];
+        platform.targets = vec![PI];
+        let throw = Throw {
+            angle: PI - 0.2,
+            speed: MAX_THROW_SPEED,
+        };
+        platform
+            .drops
+            .push(DropBlob::new(throw, PlayColor::RED, Some(PlayColor::RED)));
+        for _ in 0..3 {
+            platform.drops.iter_mut().for_each(|drop| drop.tick(PI));
+            platform.score();
+        }
+        assert_eq!(platform.scores, vec![(PlayColor::RED, 1)]);
+        assert!(platform.drops.is_empty());
+    }
+
+    #[test]
+    fn drop_elsewhere_does_not_score() {
+        let mut platform = PlatformDrop::new(DropPhysics::default());
+        platform.scores = vec![(PlayColor::RED, 0)];
+        platform.targets = vec![PI];
+        let throw = Throw {
+            angle: PI / 2.,
+            speed: 0.,
+        };
+        platform
+            .drops
+            .push(DropBlob::new(throw, PlayColor::RED, Some(PlayColor::RED)));
+        platform.drops.iter_mut().for_each(|drop| drop.tick(PI));
+        platform.score();
+        assert_eq!(platform.scores, vec![(PlayColor::RED, 0)]);
+    }
+
+    #[test]
+    fn tilting_phones_are_told_apart() {
+        let mut platform = PlatformDrop::new(DropPhysics::default());
+        platform.gravity = 0.;
+        platform.message(MessagesDrop::Tilt("left".into(), -1.));
+        platform.message(MessagesDrop::Tilt("right".into(), 1.));
+        platform.follow_tilt();
+        assert!(platform.gravity.abs() < 1e-5);
+
+        platform.physics.tilt = Tilt::Controller;
+        platform.message(MessagesDrop::TakeControl("right".into()));
+        platform.follow_tilt();
+        assert!((platform.gravity - TILT_SPEED).abs() < 1e-5);
+    }
 }
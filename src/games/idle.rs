@@ -19,5 +19,6 @@ impl PlatformIdle {
     pub fn message(&mut self) {
         self.display.tick();
         self.display.rainbow();
+        self.display.flatten();
     }
 }
@@ -1,4 +1,10 @@
 pub mod snake;
 pub mod snake_board;
 pub mod drop;
-pub mod idle;
\ No newline at end of file
+pub mod pong;
+pub mod whack;
+pub mod simon;
+pub mod idle;
+pub mod replay;
+pub mod snake_bot;
+pub mod off;
\ No newline at end of file
@@ -0,0 +1,19 @@
+use crate::display::Display;
+
+/// Keeps the ring black, e.g. during the night.
+#[derive(Debug)]
+pub struct PlatformOff {
+    display: Display,
+}
+
+impl PlatformOff {
+    pub fn new() -> Self {
+        Self {
+            display: Display::new(),
+        }
+    }
+
+    pub fn get_circle(&self) -> String {
+        self.display.get_circle()
+    }
+}
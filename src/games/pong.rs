@@ -0,0 +1,683 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use async_std::task::sleep;
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{Game, Limited, Nicknames, PlayColor, FREQUENCY, LED_COUNT},
+    display::{Blob, Display, Layer},
+    games::{
+        snake::Winner,
+        snake_board::{Position, TurnDir},
+    },
+    server, Route,
+};
+
+#[cfg(debug_assertions)]
+const COUNTDOWN_PLAY: usize = 2;
+#[cfg(not(debug_assertions))]
+const COUNTDOWN_PLAY: usize = LED_COUNT;
+
+const COUNTDOWN_WINNER: usize = 4 * FREQUENCY;
+pub const MAX_PONG_PLAYERS: usize = 4;
+const LIFE_INIT: usize = 3;
+const PADDLE_WIDTH: usize = 9;
+/// The speeds of the ball are in LEDs per tick.
+const BALL_SPEED: f32 = 0.8;
+const BALL_SPEED_MAX: f32 = 3.;
+const BALL_SPEED_UP: f32 = 1.1;
+const SWING_DURATION: usize = FREQUENCY / 3;
+const SWING_COOLDOWN: usize = FREQUENCY / 2;
+const SERVE_PAUSE: usize = FREQUENCY;
+const WHITE: PlayColor = PlayColor::rgb(0xff, 0xff, 0xff);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum PongGame {
+    Idle,
+    Signup(Vec<PlayColor>),
+    Play(Vec<PlayColor>),
+    Winner(PlayColor),
+}
+
+/// What the phones poll: the game, the nicknames and the lives of the players.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PongStatus {
+    pub game: PongGame,
+    pub names: Nicknames,
+    pub lifes: Vec<(PlayColor, usize)>,
+}
+
+#[component]
+pub fn Pong() -> Element {
+    let mut pong = use_signal(|| PongGame::Idle);
+    let mut names = use_signal(Nicknames::default);
+    let mut lifes = use_signal(Vec::new);
+    let player: Signal<Option<PlayColor>> = use_signal(|| None);
+    let session: Signal<Option<String>> = use_signal(|| None);
+
+    use_future(move || async move {
+        loop {
+            match pong_state().await {
+                Ok(state) => {
+                    pong.set(state.game);
+                    names.set(state.names);
+                    lifes.set(state.lifes);
+                }
+                Err(e) => tracing::error!("While getting the pong state: {e:?}"),
+            }
+            sleep(Duration::from_millis(500)).await;
+        }
+    });
+
+    rsx! {
+        div {
+            Link {to: Route::Reset{}, style: "text-align: center; width: 100%;", "Home"}
+            match pong() {
+                PongGame::Idle => rsx!{JoinPong{joined: vec![], player, session}},
+                PongGame::Signup(joined) => if player().is_some() {
+                    let waiting = names().join(&joined, " : ");
+                    rsx!{div {class: "centered-div", "En attente d'autres joueurs - {waiting}"}}
+                } else if joined.len() < MAX_PONG_PLAYERS {
+                    rsx!{JoinPong{joined, player, session}}
+                } else {
+                    rsx!{div {class: "centered-div", "La partie est complète"}}
+                },
+                PongGame::Play(_) => if let (Some(player), Some(session)) = (player(), session()) {
+                    rsx!{PlayPong {player, session, lifes: lifes()}}
+                } else {
+                    rsx!{div {class: "centered-div", "Une partie est en cours"}}
+                },
+                PongGame::Winner(winner) => rsx!{Winner {winner, player: player(), names: names()}},
+            }
+        }
+    }
+}
+
+#[component]
+fn JoinPong(
+    joined: Vec<PlayColor>,
+    mut player: Signal<Option<PlayColor>>,
+    mut session: Signal<Option<String>>,
+) -> Element {
+    let mut nickname = use_signal(String::new);
+
+    use_effect(move || {
+        player.set(None);
+        session.set(None);
+    });
+
+    let join = move |color: PlayColor| async move {
+        document::eval(include_str!("../../fullscreen.js"));
+        match pong_join(color, nickname()).await {
+            Ok(Limited::Accepted(Some(s))) => {
+                session.set(Some(s));
+                player.set(Some(color));
+            }
+            Ok(Limited::Accepted(None)) => tracing::warn!("Couldn't join with {color}"),
+            Ok(Limited::Cooldown(ms)) => tracing::warn!("Joining too fast, wait {ms}ms"),
+            Err(e) => tracing::error!("While joining: {e:?}"),
+        }
+    };
+    let free: Vec<PlayColor> = PlayColor::all()
+        .into_iter()
+        .filter(|c| c.can_join(&joined))
+        .collect();
+
+    rsx! {
+        div {
+            class: "team-row",
+            input {
+                r#type: "text",
+                class: "team-block",
+                placeholder: "Pseudo",
+                maxlength: "16",
+                value: "{nickname}",
+                oninput: move |evt| nickname.set(evt.value()),
+            }
+        }
+        div {
+            id: "player-grid",
+
+            for color in free {
+                button {onclick: move |_| async move {join(color).await},
+                    class:"color-block", style:"background-color: #{color.to_hex_pastel()};",
+                    "{color.to_string()}"
+                }
+            }
+        }
+    }
+}
+
+/// The paddle moves while a side is held down, and hits the ball with the middle button.
+#[component]
+fn PlayPong(player: PlayColor, session: String, lifes: Vec<(PlayColor, usize)>) -> Element {
+    let session = use_signal(|| session);
+    let hearts = lifes
+        .iter()
+        .find(|(c, _)| c == &player)
+        .map_or(String::new(), |(_, l)| "♥".repeat(*l));
+
+    async fn paddle(session: String, dir: Option<TurnDir>) {
+        match pong_move(session, dir).await {
+            Ok(Limited::Accepted(_)) => {}
+            Ok(Limited::Cooldown(ms)) => tracing::warn!("Moving too fast, wait {ms}ms"),
+            Err(e) => tracing::error!("While calling pong_move: {e:?}"),
+        }
+    }
+
+    rsx! {
+        div {
+            class: "snakeContainer",
+            style: "background-color: #{player.to_hex_pastel()};",
+            div {
+                class: "snakeDivision",
+                onmousedown: move |_| async move {paddle(session(), Some(TurnDir::Left)).await},
+                onmouseout: move |_| async move {paddle(session(), None).await},
+                onmouseup: move |_| async move {paddle(session(), None).await},
+                span {
+                    class: "snakeSymbol",
+                    "↺"
+                }
+            }
+            div {
+                class: "snakeDivision snakeJump",
+                onclick: move |_| async move {
+                    if let Err(e) = pong_swing(session()).await {
+                        tracing::error!("While calling pong_swing: {e:?}");
+                    }
+                },
+                span {
+                    class: "snakeSymbol",
+                    "{hearts}"
+                }
+            }
+            div {
+                class: "snakeDivision",
+                onmousedown: move |_| async move {paddle(session(), Some(TurnDir::Right)).await},
+                onmouseout: move |_| async move {paddle(session(), None).await},
+                onmouseup: move |_| async move {paddle(session(), None).await},
+                span {
+                    class: "snakeSymbol",
+                    "↻"
+                }
+            }
+        }
+    }
+}
+
+/// The controls of the players carry the session returned when joining.
+pub enum MessagesPong {
+    Join(PlayColor, String),
+    Move(String, Option<TurnDir>),
+    Swing(String),
+    GetState,
+    Tick,
+}
+
+pub enum AnswerPong {
+    Joined(Option<String>),
+    State(PongStatus),
+}
+
+/// Every player defends a sector of the ring with a paddle.
+/// Like in the snake game, a second player starts the countdown.
+#[derive(Debug)]
+pub struct PlatformPong {
+    display: Display,
+    game: PongGame,
+    countdown: usize,
+    court: Option<Court>,
+    sessions: HashMap<String, PlayColor>,
+    names: Nicknames,
+    last_input: Instant,
+}
+
+impl PlatformPong {
+    pub fn new() -> Self {
+        Self {
+            display: Display::new(),
+            game: PongGame::Idle,
+            countdown: 0,
+            court: None,
+            sessions: HashMap::new(),
+            names: Nicknames::default(),
+            last_input: Instant::now(),
+        }
+    }
+
+    pub fn get_circle(&self) -> String {
+        self.display.get_circle()
+    }
+
+    /// Nobody signed up nor plays.
+    pub fn is_idle(&self) -> bool {
+        matches!(self.game, PongGame::Idle)
+    }
+
+    pub fn has_session(&self, session: &str) -> bool {
+        self.sessions.contains_key(session)
+    }
+
+    pub fn last_input(&self) -> Instant {
+        self.last_input
+    }
+
+    pub fn message(&mut self, msg: MessagesPong) -> Option<AnswerPong> {
+        if !matches!(msg, MessagesPong::Tick | MessagesPong::GetState) {
+            self.last_input = Instant::now();
+        }
+        match msg {
+            MessagesPong::Join(color, name) => {
+                return Some(AnswerPong::Joined(self.join(color, &name)));
+            }
+            MessagesPong::Move(session, dir) => {
+                if let Some(paddle) = self.paddle(&session) {
+                    paddle.turn = dir;
+                }
+            }
+            MessagesPong::Swing(session) => {
+                if let Some(paddle) = self.paddle(&session) {
+                    paddle.swing();
+                }
+            }
+            MessagesPong::GetState => {
+                return Some(AnswerPong::State(PongStatus {
+                    game: self.game.clone(),
+                    names: self.names.clone(),
+                    lifes: self.court.as_ref().map_or(vec![], |c| c.lifes()),
+                }))
+            }
+            MessagesPong::Tick => self.tick(),
+        }
+        None
+    }
+
+    fn paddle(&mut self, session: &str) -> Option<&mut Paddle> {
+        let c = *self.sessions.get(session)?;
+        self.court.as_mut()?.paddle(c)
+    }
+
+    fn join(&mut self, c: PlayColor, name: &str) -> Option<String> {
+        let joined = match &self.game {
+            PongGame::Idle => vec![],
+            PongGame::Signup(joined) if joined.len() < MAX_PONG_PLAYERS => joined.clone(),
+            _ => return None,
+        };
+        if !c.can_join(&joined) {
+            return None;
+        }
+        let joined = [joined, vec![c]].concat();
+        if joined.len() > 1 {
+            self.countdown = COUNTDOWN_PLAY;
+        }
+        self.game = PongGame::Signup(joined);
+        let session = format!("{:032x}", rand::random::<u128>());
+        self.sessions.insert(session.clone(), c);
+        self.names.set(c, name);
+        Some(session)
+    }
+
+    fn tick(&mut self) {
+        self.display.tick();
+
+        match self.game.clone() {
+            PongGame::Idle => self.display.rainbow(),
+            PongGame::Signup(players) => self.display.game_signup(players, self.countdown),
+            PongGame::Play(_) => {
+                if let Some(court) = self.court.as_mut() {
+                    court.tick();
+                    self.display.clear();
+                    self.display.draw_blobs(Layer::Entities, court.blobs());
+                    let alive = court.alive();
+                    if alive.len() < 2 {
+                        self.game = PongGame::Winner(alive.first().copied().unwrap_or(WHITE));
+                        self.countdown = COUNTDOWN_WINNER;
+                    }
+                }
+            }
+            PongGame::Winner(winner) => self.display.game_winner(winner, self.countdown),
+        }
+
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            if self.countdown == 0 {
+                self.game = match self.game.clone() {
+                    PongGame::Signup(players) => {
+                        self.court = Some(Court::new(&players));
+                        self.display.reset();
+                        PongGame::Play(players)
+                    }
+                    _ => {
+                        self.court = None;
+                        self.sessions.clear();
+                        self.names = Nicknames::default();
+                        PongGame::Idle
+                    }
+                }
+            }
+        }
+        self.display.flatten();
+    }
+}
+
+/// The sectors of the players split the ring equally.
+/// The sectors of the players without lives left reflect the ball like a wall.
+#[derive(Debug)]
+struct Court {
+    paddles: Vec<Paddle>,
+    ball: Ball,
+    sector_width: usize,
+    /// Ticks until the ball is served.
+    serve: usize,
+}
+
+#[derive(Debug)]
+struct Paddle {
+    color: PlayColor,
+    start: usize,
+    pos: Position,
+    turn: Option<TurnDir>,
+    swing: usize,
+    recover: usize,
+    lifes: usize,
+}
+
+/// The ball is `live` for the sector it entered, until the player hits it back.
+#[derive(Debug)]
+struct Ball {
+    pos: f32,
+    speed: f32,
+    sector: usize,
+    live: bool,
+}
+
+impl Court {
+    fn new(players: &[PlayColor]) -> Self {
+        let sector_width = LED_COUNT / players.len();
+        let paddles = players
+            .iter()
+            .enumerate()
+            .map(|(i, color)| Paddle {
+                color: *color,
+                start: i * sector_width,
+                pos: Position(i * sector_width + sector_width / 2),
+                turn: None,
+                swing: 0,
+                recover: 0,
+                lifes: LIFE_INIT,
+            })
+            .collect();
+        let mut court = Self {
+            paddles,
+            ball: Ball {
+                pos: 0.,
+                speed: 0.,
+                sector: 0,
+                live: false,
+            },
+            sector_width,
+            serve: 0,
+        };
+        court.serve_from(rand::random::<usize>() % players.len());
+        court
+    }
+
+    fn paddle(&mut self, c: PlayColor) -> Option<&mut Paddle> {
+        self.paddles
+            .iter_mut()
+            .find(|p| p.color == c && p.lifes > 0)
+    }
+
+    fn alive(&self) -> Vec<PlayColor> {
+        self.paddles
+            .iter()
+            .filter(|p| p.lifes > 0)
+            .map(|p| p.color)
+            .collect()
+    }
+
+    fn lifes(&self) -> Vec<(PlayColor, usize)> {
+        self.paddles.iter().map(|p| (p.color, p.lifes)).collect()
+    }
+
+    /// The ball waits on the paddle, and then goes in a random direction.
+    fn serve_from(&mut self, sector: usize) {
+        let direction = if rand::random() { 1. } else { -1. };
+        self.ball = Ball {
+            pos: self.paddles[sector].pos.0 as f32,
+            speed: BALL_SPEED * direction,
+            sector,
+            live: false,
+        };
+        self.serve = SERVE_PAUSE;
+    }
+
+    fn sector_of(&self, pos: f32) -> usize {
+        (pos as usize / self.sector_width).min(self.paddles.len() - 1)
+    }
+
+    fn tick(&mut self) {
+        let width = self.sector_width as i32;
+        for paddle in self.paddles.iter_mut().filter(|p| p.lifes > 0) {
+            paddle.tick(width);
+        }
+        if self.serve > 0 {
+            self.serve -= 1;
+            return;
+        }
+
+        let previous = self.ball.pos;
+        self.ball.pos = (self.ball.pos + self.ball.speed).rem_euclid(LED_COUNT as f32);
+        let sector = self.sector_of(self.ball.pos);
+        if sector != self.ball.sector {
+            if self.ball.live {
+                let loser = self.ball.sector;
+                self.paddles[loser].lifes -= 1;
+                let server = (0..self.paddles.len())
+                    .map(|i| (loser + i) % self.paddles.len())
+                    .find(|i| self.paddles[*i].lifes > 0)
+                    .unwrap_or(loser);
+                self.serve_from(server);
+                return;
+            }
+            if self.paddles[sector].lifes == 0 {
+                self.ball.pos = previous;
+                self.ball.speed = -self.ball.speed;
+            } else {
+                self.ball.sector = sector;
+            }
+            self.ball.live = true;
+        }
+
+        let ball = Position(self.ball.pos.round() as usize % LED_COUNT);
+        let paddle = &self.paddles[self.ball.sector];
+        let reach = (PADDLE_WIDTH / 2) as i32 + self.ball.speed.abs().ceil() as i32;
+        if self.ball.live && paddle.swing > 0 && paddle.pos.direction(ball).abs() <= reach {
+            self.ball.speed =
+                (-self.ball.speed * BALL_SPEED_UP).clamp(-BALL_SPEED_MAX, BALL_SPEED_MAX);
+            self.ball.live = false;
+        }
+    }
+
+    fn blobs(&self) -> Vec<Blob> {
+        let mut blobs: Vec<Blob> = self
+            .paddles
+            .iter()
+            .map(|p| {
+                let center = p.start as f32 + self.sector_width as f32 / 2.;
+                Blob::Sector(center, self.sector_width as f32, p.color, p.lifes)
+            })
+            .collect();
+        for p in self.paddles.iter().filter(|p| p.lifes > 0) {
+            let color = if p.swing > 0 { WHITE } else { p.color };
+            blobs.push(Blob::Drop(p.pos.0 as f32, color, PADDLE_WIDTH as f32));
+        }
+        if self.serve % (FREQUENCY / 5) < FREQUENCY / 10 {
+            blobs.push(Blob::Drop(self.ball.pos, WHITE, 2.));
+        }
+        blobs
+    }
+}
+
+impl Paddle {
+    /// The paddle stays inside of its sector.
+    fn tick(&mut self, sector_width: i32) {
+        if let Some(dir) = &self.turn {
+            let step = if matches!(dir, TurnDir::Left) { -1 } else { 1 };
+            let new_pos = self.pos.add(step);
+            let offset = ((new_pos.0 + LED_COUNT - self.start) % LED_COUNT) as i32;
+            let half = (PADDLE_WIDTH / 2) as i32;
+            if offset >= half && offset < sector_width - half {
+                self.pos = new_pos;
+            }
+        }
+        if self.swing > 0 {
+            self.swing -= 1;
+        }
+        if self.recover > 0 {
+            self.recover -= 1;
+        }
+    }
+
+    fn swing(&mut self) {
+        if self.recover == 0 {
+            self.swing = SWING_DURATION;
+            self.recover = SWING_DURATION + SWING_COOLDOWN;
+        }
+    }
+}
+
+#[server(endpoint = "pong/join")]
+async fn pong_join(
+    color: PlayColor,
+    name: String,
+) -> Result<Limited<Option<String>>, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if let Err(wait) = plat.rate_limit(Game::Pong, &crate::client_ip().await?) {
+        return Ok(Limited::Cooldown(wait.as_millis() as u64));
+    }
+    match plat.pong_message(MessagesPong::Join(color, name)) {
+        Some(AnswerPong::Joined(session)) => Ok(Limited::Accepted(session)),
+        _ => Ok(Limited::Accepted(None)),
+    }
+}
+
+#[server(endpoint = "pong/move")]
+async fn pong_move(session: String, dir: Option<TurnDir>) -> Result<Limited<()>, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if let Err(wait) = plat.rate_limit_session(Game::Pong, &session) {
+        return Ok(Limited::Cooldown(wait.as_millis() as u64));
+    }
+    plat.pong_message(MessagesPong::Move(session, dir));
+    Ok(Limited::Accepted(()))
+}
+
+#[server(endpoint = "pong/swing")]
+async fn pong_swing(session: String) -> Result<Limited<()>, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if let Err(wait) = plat.rate_limit_session(Game::Pong, &session) {
+        return Ok(Limited::Cooldown(wait.as_millis() as u64));
+    }
+    plat.pong_message(MessagesPong::Swing(session));
+    Ok(Limited::Accepted(()))
+}
+
+#[server(endpoint = "pong/state")]
+async fn pong_state() -> Result<PongStatus, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if let Some(AnswerPong::State(state)) = plat.pong_message(MessagesPong::GetState) {
+        Ok(state)
+    } else {
+        Err(ServerFnError::ServerError("didn't get state".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLUE: PlayColor = PlayColor::rgb(0x00, 0x00, 0xff);
+
+    /// Two players, with the sectors 0..144 and 144..288, and the ball in play.
+    fn rally(pos: f32, speed: f32, live: bool) -> Court {
+        let mut court = Court::new(&[PlayColor::RED, BLUE]);
+        court.ball = Ball {
+            pos,
+            speed,
+            sector: 0,
+            live,
+        };
+        court.serve = 0;
+        court
+    }
+
+    #[test]
+    fn ball_waits_before_the_serve() {
+        let mut court = rally(0., 0., false);
+        court.serve_from(1);
+        assert_eq!(court.ball.pos, 216.);
+        for _ in 0..SERVE_PAUSE {
+            court.tick();
+        }
+        assert_eq!(court.ball.pos, 216.);
+        court.tick();
+        assert!(((court.ball.pos - 216.).abs() - BALL_SPEED).abs() < 1e-3);
+        assert!(!court.ball.live);
+    }
+
+    #[test]
+    fn missed_ball_costs_a_life() {
+        let mut court = rally(143.5, BALL_SPEED, true);
+        court.tick();
+        assert_eq!(court.lifes(), vec![(PlayColor::RED, 2), (BLUE, 3)]);
+        assert_eq!(court.ball.pos, 72.);
+        assert_eq!(court.serve, SERVE_PAUSE);
+    }
+
+    #[test]
+    fn ball_enters_the_next_sector() {
+        let mut court = rally(143.5, BALL_SPEED, false);
+        court.tick();
+        assert_eq!(court.ball.sector, 1);
+        assert!(court.ball.live);
+        assert_eq!(court.lifes(), vec![(PlayColor::RED, 3), (BLUE, 3)]);
+    }
+
+    #[test]
+    fn eliminated_sector_bounces_the_ball() {
+        let mut court = rally(143.5, BALL_SPEED, false);
+        court.paddles[1].lifes = 0;
+        court.tick();
+        assert_eq!(court.ball.pos, 143.5);
+        assert_eq!(court.ball.speed, -BALL_SPEED);
+        assert_eq!(court.ball.sector, 0);
+        assert!(court.ball.live);
+        assert_eq!(court.alive(), vec![PlayColor::RED]);
+    }
+
+    #[test]
+    fn hits_speed_up_the_ball_up_to_a_maximum() {
+        let mut court = rally(70., BALL_SPEED, true);
+        court.paddles[0].swing();
+        court.tick();
+        assert_eq!(court.ball.speed, -BALL_SPEED * BALL_SPEED_UP);
+        assert!(!court.ball.live);
+
+        let mut court = rally(70., BALL_SPEED_MAX - 0.1, true);
+        court.paddles[0].swing();
+        court.tick();
+        assert_eq!(court.ball.speed, -BALL_SPEED_MAX);
+    }
+
+    #[test]
+    fn no_hit_without_swing() {
+        let mut court = rally(70., BALL_SPEED, true);
+        court.tick();
+        assert_eq!(court.ball.speed, BALL_SPEED);
+        assert!(court.ball.live);
+    }
+}
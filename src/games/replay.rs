@@ -0,0 +1,280 @@
+use std::time::{Duration, Instant};
+
+use async_std::task::sleep;
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{Game, Limited, Nicknames},
+    display::Display,
+    games::{
+        snake::SnakeGame,
+        snake_board::{Board, MatchHistory, MatchLog},
+    },
+    server, Route,
+};
+
+const SPEEDS: [f32; 5] = [0.25, 0.5, 1., 2., 4.];
+
+/// Lets the phones choose a logged snake match, and control its replay.
+#[component]
+pub fn Replay() -> Element {
+    let mut state = use_signal(ReplayState::default);
+
+    use_future(move || async move {
+        loop {
+            match replay_message(MessagesReplay::GetState).await {
+                Ok(Limited::Accepted(AnswerReplay::State(s))) => state.set(s),
+                Ok(Limited::Cooldown(_)) => {}
+                Err(e) => tracing::error!("While getting replay state: {e:?}"),
+            }
+            sleep(Duration::from_millis(500)).await;
+        }
+    });
+
+    let send = move |msg: MessagesReplay| async move {
+        match replay_message(msg).await {
+            Ok(Limited::Accepted(AnswerReplay::State(s))) => state.set(s),
+            Ok(Limited::Cooldown(ms)) => tracing::warn!("Too many replay controls, wait {ms}ms"),
+            Err(e) => tracing::error!("While controlling the replay: {e:?}"),
+        }
+    };
+
+    let ReplayState {
+        matches,
+        current,
+        tick,
+        ticks,
+        paused,
+        speed,
+    } = state();
+
+    rsx! {
+        div {
+            class: "centered-div",
+            Link {to: Route::Reset{}, "Home"}
+            div {
+                for (i, players) in matches.into_iter().enumerate() {
+                    button {onclick: move |_| async move {send(MessagesReplay::Start(i)).await},
+                        style: if current == Some(i) { "font-weight: bold;" } else { "" },
+                        "Match {i + 1}: "
+                        for (p, name) in players.0 {
+                            span {style: "color: #{p.to_hex()};", "● "}
+                            "{name} "
+                        }
+                    }
+                    br{}
+                }
+            }
+            if current.is_some() {
+                div {
+                    button {onclick: move |_| async move {send(MessagesReplay::Pause(!paused)).await},
+                        if paused { "▶" } else { "⏸" }
+                    }
+                    for s in SPEEDS {
+                        button {onclick: move |_| async move {send(MessagesReplay::Speed(s)).await},
+                            style: if speed == s { "font-weight: bold;" } else { "" },
+                            "{s}x"
+                        }
+                    }
+                    br{}
+                    input {
+                        r#type: "range",
+                        min: "0",
+                        max: "{ticks}",
+                        value: "{tick}",
+                        onchange: move |evt| async move {
+                            if let Ok(tick) = evt.value().parse() {
+                                send(MessagesReplay::Seek(tick)).await;
+                            }
+                        },
+                    }
+                    "{tick} / {ticks}"
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum MessagesReplay {
+    Tick,
+    GetState,
+    Start(usize),
+    Pause(bool),
+    Speed(f32),
+    Seek(usize),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AnswerReplay {
+    State(ReplayState),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ReplayState {
+    pub matches: Vec<Nicknames>,
+    pub current: Option<usize>,
+    pub tick: usize,
+    pub ticks: usize,
+    pub paused: bool,
+    pub speed: f32,
+}
+
+#[derive(Debug)]
+pub struct PlatformReplay {
+    display: Display,
+    history: MatchHistory,
+    current: Option<(usize, MatchLog)>,
+    board: Option<Board>,
+    game: SnakeGame,
+    paused: bool,
+    speed: f32,
+    steps: f32,
+    last_input: Instant,
+}
+
+impl PlatformReplay {
+    pub fn new(history: MatchHistory) -> Self {
+        Self {
+            display: Display::new(),
+            history,
+            current: None,
+            board: None,
+            game: SnakeGame::Idle,
+            paused: false,
+            speed: 1.,
+            steps: 0.,
+            last_input: Instant::now(),
+        }
+    }
+
+    pub fn get_circle(&self) -> String {
+        self.display.get_circle()
+    }
+
+    /// No replay is running.
+    pub fn is_idle(&self) -> bool {
+        self.current.is_none() || self.paused
+    }
+
+    pub fn last_input(&self) -> Instant {
+        self.last_input
+    }
+
+    pub fn message(&mut self, msg: MessagesReplay) -> Option<AnswerReplay> {
+        if !matches!(msg, MessagesReplay::Tick | MessagesReplay::GetState) {
+            self.last_input = Instant::now();
+        }
+        match msg {
+            MessagesReplay::Tick => {
+                self.tick();
+                return None;
+            }
+            MessagesReplay::GetState => {}
+            MessagesReplay::Start(i) => {
+                let log = self.history.lock().unwrap().get(i).cloned();
+                if let Some(log) = log {
+                    self.current = Some((i, log));
+                    self.paused = false;
+                    self.seek(0);
+                }
+            }
+            MessagesReplay::Pause(paused) => self.paused = paused,
+            MessagesReplay::Speed(speed) => self.speed = speed.clamp(0., 10.),
+            MessagesReplay::Seek(tick) => self.seek(tick),
+        }
+        Some(AnswerReplay::State(self.state()))
+    }
+
+    fn state(&self) -> ReplayState {
+        ReplayState {
+            matches: self
+                .history
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|log| {
+                    Nicknames(
+                        log.players
+                            .iter()
+                            .map(|p| (*p, log.names.get(*p)))
+                            .collect(),
+                    )
+                })
+                .collect(),
+            current: self.current.as_ref().map(|(i, _)| *i),
+            tick: self.board.as_ref().map_or(0, |b| b.ticks()),
+            ticks: self.current.as_ref().map_or(0, |(_, log)| log.ticks),
+            paused: self.paused,
+            speed: self.speed,
+        }
+    }
+
+    fn tick(&mut self) {
+        if self.board.is_none() {
+            self.display.tick();
+            self.display.rainbow();
+        } else if !self.paused {
+            self.steps += self.speed;
+            while self.steps >= 1. {
+                self.steps -= 1.;
+                self.step();
+            }
+        }
+        self.display.flatten();
+    }
+
+    /// Restarts the match and runs it up to the given tick.
+    fn seek(&mut self, tick: usize) {
+        let Some((_, log)) = self.current.as_ref() else {
+            return;
+        };
+        let tick = tick.min(log.ticks);
+        if self.board.as_ref().is_none_or(|b| b.ticks() > tick) {
+            self.board = Some(Board::from_log(log));
+            self.display.reset();
+        }
+        while self.board.as_ref().is_some_and(|b| b.ticks() < tick) {
+            self.step();
+        }
+        self.display.flatten();
+    }
+
+    /// Applies the inputs of the current tick and advances the board by one tick.
+    fn step(&mut self) {
+        let (Some((_, log)), Some(board)) = (self.current.as_ref(), self.board.as_mut()) else {
+            return;
+        };
+        if board.ticks() >= log.ticks {
+            self.paused = true;
+            return;
+        }
+        let tick = board.ticks();
+        for (_, input) in log.inputs.iter().filter(|(t, _)| *t == tick) {
+            board.input(input.clone());
+        }
+        self.display.tick();
+        self.display.flow();
+        self.game = board.tick(&mut self.display);
+        match &self.game {
+            SnakeGame::Winner(winner) => self.display.game_winner(*winner, board.ticks()),
+            SnakeGame::TeamWinner(team, _) => self.display.game_winner(*team, board.ticks()),
+            _ => {}
+        }
+    }
+}
+
+/// Only the controls are rate limited, not the polling of the state.
+#[server(endpoint = "replay/message")]
+async fn replay_message(msg: MessagesReplay) -> Result<Limited<AnswerReplay>, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if !matches!(msg, MessagesReplay::GetState) {
+        if let Err(wait) = plat.rate_limit(Game::Replay, &crate::client_ip().await?) {
+            return Ok(Limited::Cooldown(wait.as_millis() as u64));
+        }
+    }
+    plat.replay_message(msg)
+        .map(Limited::Accepted)
+        .ok_or(ServerFnError::ServerError("no replay running".into()))
+}
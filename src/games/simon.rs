@@ -0,0 +1,721 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use async_std::task::sleep;
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{Game, Limited, Nicknames, PlayColor, FREQUENCY, LED_COUNT, MAX_PLAYERS},
+    display::{Blob, Display, Layer},
+    games::snake::{ColorGrid, Winner},
+    server, Route,
+};
+
+#[cfg(debug_assertions)]
+const COUNTDOWN_PLAY: usize = 2;
+#[cfg(not(debug_assertions))]
+const COUNTDOWN_PLAY: usize = LED_COUNT;
+
+const COUNTDOWN_OVER: usize = 4 * FREQUENCY;
+/// Every color of the sequence is lit for `STEP_ON`, then the ring stays dark for `STEP_OFF`.
+const STEP_ON: usize = 2 * FREQUENCY / 3;
+const STEP_OFF: usize = FREQUENCY / 4;
+const SHOW_PAUSE: usize = FREQUENCY;
+const PRESS_TIMEOUT: usize = 5 * FREQUENCY;
+const FLASH: usize = FREQUENCY / 4;
+
+/// `Solo` is a single player, `Turns` lets the players repeat the sequence one after
+/// the other, and with `Together` everybody repeats it at the same time.
+/// In both multiplayer modes, a mistake eliminates the player.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum SimonMode {
+    Solo,
+    Turns,
+    Together,
+}
+
+impl SimonMode {
+    fn all() -> Vec<SimonMode> {
+        vec![Self::Solo, Self::Turns, Self::Together]
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            SimonMode::Solo => "Seul",
+            SimonMode::Turns => "Chacun son tour",
+            SimonMode::Together => "Tous ensemble",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum SimonGame {
+    Idle,
+    Signup(SimonMode, Vec<PlayColor>),
+    Play(SimonMode, Vec<PlayColor>),
+    /// The winners, more than one if they all failed in the same round,
+    /// and the longest sequence repeated.
+    Over(SimonMode, Vec<PlayColor>, usize),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SimonStatus {
+    pub game: SimonGame,
+    pub names: Nicknames,
+    /// The ring is playing back the sequence.
+    pub showing: bool,
+    /// The player who repeats the sequence when taking turns.
+    pub turn: Option<PlayColor>,
+    pub length: usize,
+    pub players: Vec<(PlayColor, SimonProgress)>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum SimonProgress {
+    Repeated(usize),
+    Eliminated,
+}
+
+#[component]
+pub fn Simon() -> Element {
+    let mut state = use_signal(|| None);
+    let player: Signal<Option<PlayColor>> = use_signal(|| None);
+    let session: Signal<Option<String>> = use_signal(|| None);
+
+    use_future(move || async move {
+        loop {
+            match simon_state().await {
+                Ok(s) => state.set(Some(s)),
+                Err(e) => tracing::error!("While getting the simon state: {e:?}"),
+            }
+            sleep(Duration::from_millis(300)).await;
+        }
+    });
+
+    let Some(SimonStatus { game, names, .. }) = state() else {
+        return rsx! {};
+    };
+
+    rsx! {
+        div {
+            Link {to: Route::Reset{}, style: "text-align: center; width: 100%;", "Home"}
+            match game {
+                SimonGame::Idle => rsx!{JoinSimon{joined: vec![], mode: None, player, session}},
+                SimonGame::Signup(mode, joined) => if player().is_some() {
+                    let waiting = names.join(&joined, " : ");
+                    rsx!{div {class: "centered-div", "En attente d'autres joueurs - {waiting}"}}
+                } else if mode != SimonMode::Solo && joined.len() < MAX_PLAYERS {
+                    rsx!{JoinSimon{joined, mode: Some(mode), player, session}}
+                } else {
+                    rsx!{div {class: "centered-div", "La partie est complète"}}
+                },
+                SimonGame::Play(_, _) => if let (Some(player), Some(session), Some(state)) = (player(), session(), state()) {
+                    rsx!{Repeat {player, session, state}}
+                } else {
+                    rsx!{div {class: "centered-div", "Une partie est en cours"}}
+                },
+                SimonGame::Over(SimonMode::Solo, _, best) => rsx!{
+                    div {class: "centered-div", "Tu as retenu {best} couleurs"}
+                },
+                SimonGame::Over(_, winners, best) => if let [winner] = winners[..] {
+                    rsx!{Winner {winner, player: player(), names: names.clone()}}
+                } else {
+                    let tied = names.join(&winners, ", ");
+                    rsx!{div {class: "centered-div", "Égalité entre {tied} avec {best} couleurs"}}
+                },
+            }
+        }
+    }
+}
+
+/// If `mode` is None, the first player chooses it.
+#[component]
+fn JoinSimon(
+    joined: Vec<PlayColor>,
+    mode: Option<SimonMode>,
+    mut player: Signal<Option<PlayColor>>,
+    mut session: Signal<Option<String>>,
+) -> Element {
+    let mut chosen = use_signal(|| mode.unwrap_or(SimonMode::Solo));
+    let mut nickname = use_signal(String::new);
+
+    use_effect(move || {
+        player.set(None);
+        session.set(None);
+    });
+
+    let join = move |color: PlayColor| async move {
+        document::eval(include_str!("../../fullscreen.js"));
+        match simon_join(color, chosen(), nickname()).await {
+            Ok(Limited::Accepted(Some(s))) => {
+                session.set(Some(s));
+                player.set(Some(color));
+            }
+            Ok(Limited::Accepted(None)) => tracing::warn!("Couldn't join with {color}"),
+            Ok(Limited::Cooldown(ms)) => tracing::warn!("Joining too fast, wait {ms}ms"),
+            Err(e) => tracing::error!("While joining: {e:?}"),
+        }
+    };
+    let free: Vec<PlayColor> = PlayColor::all()
+        .into_iter()
+        .filter(|c| c.can_join(&joined))
+        .collect();
+
+    rsx! {
+        if mode.is_none() {
+            div {
+                class: "team-row",
+                for m in SimonMode::all() {
+                    button {onclick: move |_| chosen.set(m),
+                        class: if chosen() == m { "team-block team-chosen" } else { "team-block" },
+                        "{m.label()}"
+                    }
+                }
+            }
+        }
+        div {
+            class: "team-row",
+            input {
+                r#type: "text",
+                class: "team-block",
+                placeholder: "Pseudo",
+                maxlength: "16",
+                value: "{nickname}",
+                oninput: move |evt| nickname.set(evt.value()),
+            }
+        }
+        ColorGrid {colors: free, onclick: join}
+    }
+}
+
+/// The buttons are the colors of the sectors of the ring.
+#[component]
+fn Repeat(player: PlayColor, session: String, state: SimonStatus) -> Element {
+    let session = use_signal(|| session);
+    let mut cooldown = use_signal(|| 0u64);
+    let progress = state
+        .players
+        .iter()
+        .find(|(c, _)| c == &player)
+        .map(|(_, p)| *p);
+
+    let press = move |color: PlayColor| async move {
+        match simon_press(session(), color).await {
+            Ok(Limited::Accepted(_)) => {}
+            Ok(Limited::Cooldown(ms)) => {
+                cooldown.set(ms);
+                sleep(Duration::from_millis(ms)).await;
+                cooldown.set(0);
+            }
+            Err(e) => tracing::error!("While calling simon_press: {e:?}"),
+        }
+    };
+
+    rsx! {
+        div {
+            class: "team-row",
+            style: "background-color: #{player.to_hex_pastel()};",
+            match (progress, state.turn) {
+                (Some(SimonProgress::Eliminated), _) => rsx!{"Éliminé"},
+                _ if cooldown() > 0 => rsx!{"Trop vite, attends {cooldown} ms"},
+                _ if state.showing => rsx!{"Regarde l'anneau"},
+                (_, Some(turn)) if turn != player => rsx!{"Au tour de {state.names.get(turn)}"},
+                (Some(SimonProgress::Repeated(done)), _) => rsx!{"À toi! {done} / {state.length}"},
+                (None, _) => rsx!{},
+            }
+        }
+        ColorGrid {colors: PlayColor::basic(), onclick: press}
+    }
+}
+
+/// The controls of the players carry the session returned when joining.
+pub enum MessagesSimon {
+    Join(PlayColor, SimonMode, String),
+    Press(String, PlayColor),
+    GetState,
+    Tick,
+}
+
+pub enum AnswerSimon {
+    Joined(Option<String>),
+    State(SimonStatus),
+}
+
+/// The ring is split into one sector per basic color, which light up
+/// when playing back the sequence, or when a player presses them.
+#[derive(Debug)]
+pub struct PlatformSimon {
+    display: Display,
+    game: SimonGame,
+    countdown: usize,
+    round: Option<Round>,
+    sessions: HashMap<String, PlayColor>,
+    names: Nicknames,
+    last_input: Instant,
+}
+
+impl PlatformSimon {
+    pub fn new() -> Self {
+        Self {
+            display: Display::new(),
+            game: SimonGame::Idle,
+            countdown: 0,
+            round: None,
+            sessions: HashMap::new(),
+            names: Nicknames::default(),
+            last_input: Instant::now(),
+        }
+    }
+
+    pub fn get_circle(&self) -> String {
+        self.display.get_circle()
+    }
+
+    /// Nobody signed up nor plays.
+    pub fn is_idle(&self) -> bool {
+        matches!(self.game, SimonGame::Idle)
+    }
+
+    pub fn has_session(&self, session: &str) -> bool {
+        self.sessions.contains_key(session)
+    }
+
+    pub fn last_input(&self) -> Instant {
+        self.last_input
+    }
+
+    pub fn message(&mut self, msg: MessagesSimon) -> Option<AnswerSimon> {
+        if !matches!(msg, MessagesSimon::Tick | MessagesSimon::GetState) {
+            self.last_input = Instant::now();
+        }
+        match msg {
+            MessagesSimon::Join(color, mode, name) => {
+                return Some(AnswerSimon::Joined(self.join(color, mode, &name)));
+            }
+            MessagesSimon::Press(session, button) => {
+                if let (Some(c), Some(round)) = (self.sessions.get(&session), self.round.as_mut()) {
+                    round.press(*c, button);
+                }
+            }
+            MessagesSimon::GetState => return Some(AnswerSimon::State(self.state())),
+            MessagesSimon::Tick => self.tick(),
+        }
+        None
+    }
+
+    fn state(&self) -> SimonStatus {
+        let round = self.round.as_ref();
+        SimonStatus {
+            game: self.game.clone(),
+            names: self.names.clone(),
+            showing: round.is_some_and(|r| matches!(r.phase, Phase::Show(_))),
+            turn: round.and_then(|r| (r.mode == SimonMode::Turns).then(|| r.players[r.turn].color)),
+            length: round.map_or(0, |r| r.sequence.len()),
+            players: round.map_or(vec![], |r| {
+                r.players
+                    .iter()
+                    .map(|p| {
+                        let progress = if p.alive {
+                            SimonProgress::Repeated(p.progress)
+                        } else {
+                            SimonProgress::Eliminated
+                        };
+                        (p.color, progress)
+                    })
+                    .collect()
+            }),
+        }
+    }
+
+    /// The first player chooses the mode, and a solo game starts right away.
+    fn join(&mut self, c: PlayColor, mode: SimonMode, name: &str) -> Option<String> {
+        let (mode, joined) = match &self.game {
+            SimonGame::Idle => (mode, vec![]),
+            SimonGame::Signup(mode, joined)
+                if *mode != SimonMode::Solo && joined.len() < MAX_PLAYERS =>
+            {
+                (*mode, joined.clone())
+            }
+            _ => return None,
+        };
+        if !c.can_join(&joined) {
+            return None;
+        }
+        let joined = [joined, vec![c]].concat();
+        if mode == SimonMode::Solo || joined.len() > 1 {
+            self.countdown = COUNTDOWN_PLAY;
+        }
+        self.game = SimonGame::Signup(mode, joined);
+        let session = format!("{:032x}", rand::random::<u128>());
+        self.sessions.insert(session.clone(), c);
+        self.names.set(c, name);
+        Some(session)
+    }
+
+    fn tick(&mut self) {
+        self.display.tick();
+
+        match self.game.clone() {
+            SimonGame::Idle => self.display.rainbow(),
+            SimonGame::Signup(_, players) => self.display.game_signup(players, self.countdown),
+            SimonGame::Play(mode, _) => {
+                if let Some(round) = self.round.as_mut() {
+                    if let Some((winners, best)) = round.tick() {
+                        self.game = SimonGame::Over(mode, winners, best);
+                        self.countdown = COUNTDOWN_OVER;
+                    }
+                    self.display.clear();
+                    self.display.draw_blobs(Layer::Entities, round.blobs());
+                }
+            }
+            SimonGame::Over(_, winners, _) => {
+                let winner = winners.first().copied().unwrap_or(PlayColor::RED);
+                self.display.game_winner(winner, self.countdown);
+            }
+        }
+
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            if self.countdown == 0 {
+                self.game = match self.game.clone() {
+                    SimonGame::Signup(mode, players) => {
+                        self.round = Some(Round::new(mode, &players));
+                        self.display.reset();
+                        SimonGame::Play(mode, players)
+                    }
+                    _ => {
+                        self.round = None;
+                        self.sessions.clear();
+                        self.names = Nicknames::default();
+                        SimonGame::Idle
+                    }
+                }
+            }
+        }
+        self.display.flatten();
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Phase {
+    /// Ticks since the playback started.
+    Show(usize),
+    /// The players who have to repeat the sequence in this round.
+    Repeat(Vec<usize>),
+}
+
+#[derive(Debug)]
+struct SimonPlayer {
+    color: PlayColor,
+    progress: usize,
+    alive: bool,
+    timeout: usize,
+}
+
+/// The sequence grows by one color every round, and with every turn when taking turns.
+#[derive(Debug)]
+struct Round {
+    mode: SimonMode,
+    sequence: Vec<PlayColor>,
+    players: Vec<SimonPlayer>,
+    turn: usize,
+    phase: Phase,
+    flash: Option<(PlayColor, usize)>,
+    best: usize,
+}
+
+impl Round {
+    fn new(mode: SimonMode, players: &[PlayColor]) -> Self {
+        let mut round = Self {
+            mode,
+            sequence: vec![],
+            players: players
+                .iter()
+                .map(|c| SimonPlayer {
+                    color: *c,
+                    progress: 0,
+                    alive: true,
+                    timeout: 0,
+                })
+                .collect(),
+            turn: 0,
+            phase: Phase::Show(0),
+            flash: None,
+            best: 0,
+        };
+        round.grow();
+        round
+    }
+
+    fn grow(&mut self) {
+        let colors = PlayColor::basic();
+        self.sequence
+            .push(colors[rand::random::<usize>() % colors.len()]);
+        self.phase = Phase::Show(0);
+    }
+
+    /// The players repeating the sequence now.
+    fn repeating(&self) -> Vec<usize> {
+        match self.mode {
+            SimonMode::Turns => vec![self.turn],
+            _ => (0..self.players.len())
+                .filter(|i| self.players[*i].alive)
+                .collect(),
+        }
+    }
+
+    fn press(&mut self, c: PlayColor, button: PlayColor) {
+        let Phase::Repeat(repeating) = &self.phase else {
+            return;
+        };
+        let Some(i) = repeating
+            .iter()
+            .copied()
+            .find(|i| self.players[*i].color == c)
+        else {
+            return;
+        };
+        let player = &mut self.players[i];
+        if !player.alive || player.progress == self.sequence.len() {
+            return;
+        }
+        self.flash = Some((button, FLASH));
+        if self.sequence[player.progress] == button {
+            player.progress += 1;
+            player.timeout = PRESS_TIMEOUT;
+            if player.progress == self.sequence.len() {
+                self.best = self.best.max(player.progress);
+            }
+        } else {
+            player.alive = false;
+        }
+    }
+
+    /// Returns the winners and the longest sequence repeated once the game is over.
+    fn tick(&mut self) -> Option<(Vec<PlayColor>, usize)> {
+        if let Some((_, ticks)) = self.flash.as_mut() {
+            *ticks -= 1;
+            if *ticks == 0 {
+                self.flash = None;
+            }
+        }
+        let length = self.sequence.len();
+        let repeating = match &mut self.phase {
+            Phase::Show(ticks) => {
+                *ticks += 1;
+                if *ticks >= SHOW_PAUSE + length * (STEP_ON + STEP_OFF) {
+                    let repeating = self.repeating();
+                    for i in &repeating {
+                        self.players[*i].progress = 0;
+                        self.players[*i].timeout = PRESS_TIMEOUT;
+                    }
+                    self.phase = Phase::Repeat(repeating);
+                }
+                return None;
+            }
+            Phase::Repeat(repeating) => repeating.clone(),
+        };
+
+        for i in &repeating {
+            let player = &mut self.players[*i];
+            if player.alive && player.progress < length {
+                player.timeout -= 1;
+                if player.timeout == 0 {
+                    player.alive = false;
+                }
+            }
+        }
+        let finished = repeating.iter().all(|i| {
+            let player = &self.players[*i];
+            !player.alive || player.progress == length
+        });
+        if !finished {
+            return None;
+        }
+
+        let alive: Vec<PlayColor> = self
+            .players
+            .iter()
+            .filter(|p| p.alive)
+            .map(|p| p.color)
+            .collect();
+        if self.players.len() == 1 && alive.is_empty() {
+            return Some((vec![self.players[0].color], self.best));
+        }
+        if self.players.len() > 1 && alive.len() <= 1 {
+            // When the last players all fail in the same round, they share the win.
+            let winners = if alive.is_empty() {
+                repeating.iter().map(|i| self.players[*i].color).collect()
+            } else {
+                alive
+            };
+            return Some((winners, self.best));
+        }
+        if self.mode == SimonMode::Turns {
+            self.turn = (1..=self.players.len())
+                .map(|k| (self.turn + k) % self.players.len())
+                .find(|i| self.players[*i].alive)
+                .unwrap_or(self.turn);
+        }
+        self.grow();
+        None
+    }
+
+    /// The lit sector of the sequence, or the one pressed last, on top of all dim sectors.
+    fn blobs(&self) -> Vec<Blob> {
+        let colors = PlayColor::basic();
+        let width = LED_COUNT as f32 / colors.len() as f32;
+        let center = |color: PlayColor| {
+            let i = colors.iter().position(|c| c == &color).unwrap_or(0);
+            (i as f32 + 0.5) * width
+        };
+        let mut blobs: Vec<Blob> = colors
+            .iter()
+            .map(|c| Blob::Sector(center(*c), width, *c, 0))
+            .collect();
+        let lit = match &self.phase {
+            Phase::Show(ticks) if *ticks >= SHOW_PAUSE => {
+                let step = (ticks - SHOW_PAUSE) / (STEP_ON + STEP_OFF);
+                let on = (ticks - SHOW_PAUSE) % (STEP_ON + STEP_OFF) < STEP_ON;
+                self.sequence.get(step).filter(|_| on).copied()
+            }
+            _ => self.flash.map(|(c, _)| c),
+        };
+        if let Some(c) = lit {
+            blobs.push(Blob::Drop(center(c), c, width));
+        }
+        blobs
+    }
+}
+
+#[server(endpoint = "simon/join")]
+async fn simon_join(
+    color: PlayColor,
+    mode: SimonMode,
+    name: String,
+) -> Result<Limited<Option<String>>, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if let Err(wait) = plat.rate_limit(Game::Simon, &crate::client_ip().await?) {
+        return Ok(Limited::Cooldown(wait.as_millis() as u64));
+    }
+    match plat.simon_message(MessagesSimon::Join(color, mode, name)) {
+        Some(AnswerSimon::Joined(session)) => Ok(Limited::Accepted(session)),
+        _ => Ok(Limited::Accepted(None)),
+    }
+}
+
+#[server(endpoint = "simon/press")]
+async fn simon_press(session: String, color: PlayColor) -> Result<Limited<()>, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if let Err(wait) = plat.rate_limit_session(Game::Simon, &session) {
+        return Ok(Limited::Cooldown(wait.as_millis() as u64));
+    }
+    plat.simon_message(MessagesSimon::Press(session, color));
+    Ok(Limited::Accepted(()))
+}
+
+#[server(endpoint = "simon/state")]
+async fn simon_state() -> Result<SimonStatus, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if let Some(AnswerSimon::State(state)) = plat.simon_message(MessagesSimon::GetState) {
+        Ok(state)
+    } else {
+        Err(ServerFnError::ServerError("didn't get state".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLUE: PlayColor = PlayColor::rgb(0x00, 0x00, 0xff);
+
+    /// Plays the sequence back until the players have to repeat it.
+    fn playback(round: &mut Round) {
+        while matches!(round.phase, Phase::Show(_)) {
+            assert_eq!(round.tick(), None);
+        }
+    }
+
+    fn repeat(round: &mut Round, player: PlayColor) {
+        for button in round.sequence.clone() {
+            round.press(player, button);
+        }
+    }
+
+    fn wrong(round: &Round) -> PlayColor {
+        let first = round.sequence[0];
+        PlayColor::basic()
+            .into_iter()
+            .find(|c| c != &first)
+            .unwrap()
+    }
+
+    #[test]
+    fn sequence_grows_when_repeated() {
+        let mut round = Round::new(SimonMode::Solo, &[PlayColor::RED]);
+        playback(&mut round);
+        repeat(&mut round, PlayColor::RED);
+        assert_eq!(round.tick(), None);
+        assert_eq!(round.sequence.len(), 2);
+        assert!(matches!(round.phase, Phase::Show(0)));
+        assert_eq!(round.best, 1);
+    }
+
+    #[test]
+    fn wrong_press_ends_the_solo_game() {
+        let mut round = Round::new(SimonMode::Solo, &[PlayColor::RED]);
+        playback(&mut round);
+        round.press(PlayColor::RED, wrong(&round));
+        assert_eq!(round.tick(), Some((vec![PlayColor::RED], 0)));
+    }
+
+    #[test]
+    fn presses_during_the_playback_are_ignored() {
+        let mut round = Round::new(SimonMode::Together, &[PlayColor::RED, BLUE]);
+        round.press(PlayColor::RED, wrong(&round));
+        playback(&mut round);
+        assert!(round.players.iter().all(|p| p.alive));
+    }
+
+    #[test]
+    fn slow_player_is_eliminated() {
+        let mut round = Round::new(SimonMode::Together, &[PlayColor::RED, BLUE]);
+        playback(&mut round);
+        repeat(&mut round, PlayColor::RED);
+        for _ in 1..PRESS_TIMEOUT {
+            assert_eq!(round.tick(), None);
+        }
+        assert_eq!(round.tick(), Some((vec![PlayColor::RED], 1)));
+        assert!(!round.players[1].alive);
+    }
+
+    #[test]
+    fn players_failing_together_share_the_win() {
+        let mut round = Round::new(SimonMode::Together, &[PlayColor::RED, BLUE]);
+        playback(&mut round);
+        round.press(PlayColor::RED, wrong(&round));
+        assert_eq!(round.tick(), None);
+        round.press(BLUE, wrong(&round));
+        assert_eq!(round.tick(), Some((vec![PlayColor::RED, BLUE], 0)));
+    }
+
+    #[test]
+    fn players_take_turns() {
+        let mut round = Round::new(SimonMode::Turns, &[PlayColor::RED, BLUE]);
+        playback(&mut round);
+        assert_eq!(round.phase, Phase::Repeat(vec![0]));
+        repeat(&mut round, BLUE);
+        assert!(round.players.iter().all(|p| p.progress == 0));
+        repeat(&mut round, PlayColor::RED);
+        assert_eq!(round.tick(), None);
+        assert_eq!(round.turn, 1);
+
+        playback(&mut round);
+        round.press(BLUE, wrong(&round));
+        assert_eq!(round.tick(), Some((vec![PlayColor::RED], 1)));
+    }
+}
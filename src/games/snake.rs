@@ -1,8 +1,11 @@
 use std::time::Duration;
 
 use crate::{
-    common::{PlayColor, LED_COUNT},
-    games::snake_board::{AnswerSnake, MessagesSnake, TurnDir},
+    common::{Game, Limited, Nicknames, PlayColor, Team, LED_COUNT, MAX_PLAYERS},
+    games::{
+        snake_board::{AnswerSnake, MessagesSnake, TurnDir},
+        snake_bot::Difficulty,
+    },
     server, Route,
 };
 use async_std::task::sleep;
@@ -13,20 +16,48 @@ use serde::{Deserialize, Serialize};
 pub enum SnakeGame {
     Idle,
     Signup(Vec<PlayColor>),
+    TeamSignup(usize, Vec<(PlayColor, Team)>),
     Play(Vec<PlayColor>),
     Winner(PlayColor),
-    Draw,
+    TeamWinner(Team, Vec<PlayColor>),
+    /// Nobody won, with the players of the match.
+    Draw(Vec<PlayColor>),
+}
+
+/// What the phones poll: the game, the nicknames of the players,
+/// and the nicknames with the most wins in the match history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnakeStatus {
+    pub game: SnakeGame,
+    pub names: Nicknames,
+    pub leaderboard: Vec<(String, usize)>,
 }
 
 /// Main Choice
 #[component]
 pub fn Snake() -> Element {
     let mut snake = use_signal(|| SnakeGame::Idle);
-    let current_player: Signal<Option<PlayColor>> = use_signal(|| None);
+    let mut names = use_signal(Nicknames::default);
+    let mut leaderboard = use_signal(Vec::new);
+    let mut current_player: Signal<Option<PlayColor>> = use_signal(|| None);
+    let mut session: Signal<Option<String>> = use_signal(|| None);
 
     use_future(move || async move {
+        // A phone which reloads the page gets its color back if the match is still going.
+        if let Ok(Some(s)) = document::eval("return localStorage.getItem('snake_session');")
+            .join::<Option<String>>()
+            .await
+        {
+            if let Ok(Some(player)) = snake_rejoin(s.clone()).await {
+                session.set(Some(s));
+                current_player.set(Some(player));
+            }
+        }
         loop {
-            snake.set(snake_state().await.unwrap());
+            let state = snake_state().await.unwrap();
+            snake.set(state.game);
+            names.set(state.names);
+            leaderboard.set(state.leaderboard);
             sleep(Duration::from_millis(500)).await;
         }
     });
@@ -35,43 +66,140 @@ pub fn Snake() -> Element {
         div {
             Link {to: Route::Reset{}, style: "text-align: center; width: 100%;", "Home"}
             match snake() {
-                SnakeGame::Idle => rsx!{Join{joined: vec![], current_player}},
+                SnakeGame::Idle => rsx!{Join{joined: vec![], teams: None, current_player, session}},
                 SnakeGame::Signup(joined) => if current_player().is_some() {
-                    rsx!{WaitJoin{ joined }}
+                    rsx!{WaitJoin{ joined, teams: vec![], names: names() }}
+                } else {
+                    rsx!{Join{joined, teams: Some(1), current_player, session}}
+                },
+                SnakeGame::TeamSignup(count, teams) => if current_player().is_some() {
+                    rsx!{WaitJoin{ joined: teams.iter().map(|(p, _)| *p).collect(), teams, names: names() }}
                 } else {
-                    rsx!{Join{joined, current_player}}
+                    rsx!{Join{joined: teams.iter().map(|(p, _)| *p).collect(), teams: Some(count), current_player, session}}
                 },
-                SnakeGame::Play(players) => if let Some(player) = current_player() {
-                    rsx!{Play { players, player }}
+                SnakeGame::Play(players) => if let (Some(player), Some(session)) = (current_player(), session()) {
+                    rsx!{Play { players, player, session }}
                 } else {
-                    rsx!{WaitWinner {  }}
+                    rsx!{WaitWinner { players, names: names() }}
                 },
-                SnakeGame::Winner(winner) => rsx!{Winner {winner, player: current_player() }},
-                SnakeGame::Draw => rsx!{Draw {}},
+                SnakeGame::Winner(winner) => rsx!{Winner {winner, player: current_player(), names: names() }},
+                SnakeGame::TeamWinner(team, members) => rsx!{TeamWinner {team, members, player: current_player(), names: names() }},
+                SnakeGame::Draw(players) => rsx!{Draw { players, names: names() }},
+            }
+            if !leaderboard().is_empty() && matches!(snake(), SnakeGame::Winner(_) | SnakeGame::TeamWinner(_, _) | SnakeGame::Draw(_)) {
+                Leaderboard { leaderboard: leaderboard() }
             }
         }
     }
 }
 
+/// `teams` is the number of teams, 1 meaning everybody plays for themselves.
+/// If it is None, the first player chooses.
 #[component]
-fn Join(joined: Vec<PlayColor>, current_player: Signal<Option<PlayColor>>) -> Element {
+fn Join(
+    joined: Vec<PlayColor>,
+    teams: Option<usize>,
+    current_player: Signal<Option<PlayColor>>,
+    session: Signal<Option<String>>,
+) -> Element {
+    let mut count = use_signal(|| teams.unwrap_or(1));
+    let mut team = use_signal(|| Team::One);
+    let mut wheel = use_signal(|| PlayColor::RED);
+    let mut nickname = use_signal(String::new);
+    let mut rejected = use_signal(|| false);
+
     use_effect(move || {
         current_player.set(None);
+        session.set(None);
     });
 
     let join = move |player: PlayColor| async move {
         document::eval(include_str!("../../fullscreen.js"));
-        if snake_join(player).await.unwrap() {
-            current_player.set(Some(player));
+        let answer = if count() > 1 {
+            snake_join_team(player, count(), team(), nickname()).await
+        } else {
+            snake_join(player, nickname()).await
+        };
+        match answer {
+            Ok(Limited::Accepted(Some(s))) => {
+                document::eval(&format!("localStorage.setItem('snake_session', '{s}');"));
+                session.set(Some(s));
+                current_player.set(Some(player));
+            }
+            Ok(Limited::Accepted(None)) => rejected.set(true),
+            Ok(Limited::Cooldown(ms)) => tracing::warn!("Joining too fast, wait {ms}ms"),
+            Err(e) => tracing::error!("While joining: {e:?}"),
         }
     };
+    let free: Vec<PlayColor> = PlayColor::all()
+        .into_iter()
+        .filter(|c| c.can_join(&joined))
+        .collect();
+
+    rsx! {
+        if teams.is_none() {
+            div {
+                class: "team-row",
+                for (c, label) in [(1, "Chacun pour soi"), (2, "2 équipes"), (3, "3 équipes")] {
+                    button {onclick: move |_| count.set(c),
+                        class: if count() == c { "team-block team-chosen" } else { "team-block" },
+                        "{label}"
+                    }
+                }
+            }
+        }
+        if count() > 1 {
+            div {
+                class: "team-row",
+                for t in Team::all(count()) {
+                    button {onclick: move |_| team.set(t),
+                        class: if team() == t { "team-block team-chosen" } else { "team-block" },
+                        style: "background-color: #{t.to_hex()};",
+                        "{t.name()}"
+                    }
+                }
+            }
+        }
+        div {
+            class: "team-row",
+            input {
+                r#type: "text",
+                class: "team-block",
+                placeholder: "Pseudo",
+                maxlength: "16",
+                value: "{nickname}",
+                oninput: move |evt| nickname.set(evt.value()),
+            }
+            input {
+                r#type: "color",
+                class: "team-block",
+                value: "#{wheel().to_hex()}",
+                oninput: move |evt| {
+                    if let Some(c) = PlayColor::from_hex(&evt.value()) {
+                        wheel.set(c);
+                        rejected.set(false);
+                    }
+                },
+            }
+            button {onclick: move |_| async move {join(wheel()).await},
+                class: "team-block",
+                style: "background-color: #{wheel().to_hex_pastel()};",
+                if rejected() { "Couleur trop proche d'un autre joueur" } else { "Ma couleur" }
+            }
+        }
+        ColorGrid {colors: free, onclick: join}
+    }
+}
 
+/// The grid of color blocks to choose a color from, or to press as buttons.
+#[component]
+pub fn ColorGrid(colors: Vec<PlayColor>, onclick: EventHandler<PlayColor>) -> Element {
     rsx! {
         div {
-            id: "color-grid",
+            id: "player-grid",
 
-            for color in PlayColor::all() {
-                button {onclick: move |_| async move {join(color).await},
+            for color in colors {
+                button {onclick: move |_| onclick.call(color),
                     class:"color-block", style:"background-color: #{color.to_hex_pastel()};",
                     "{color.to_string()}"
                 }
@@ -81,45 +209,98 @@ fn Join(joined: Vec<PlayColor>, current_player: Signal<Option<PlayColor>>) -> El
 }
 
 #[component]
-fn WaitJoin(joined: Vec<PlayColor>) -> Element {
-    let colors: Vec<String> = joined
-        .iter()
-        .map(|j| j.to_string())
-        .collect::<Vec<String>>();
-    let colors_str = colors.join(" : ");
+fn WaitJoin(joined: Vec<PlayColor>, teams: Vec<(PlayColor, Team)>, names: Nicknames) -> Element {
+    let colors_str = if teams.is_empty() {
+        names.join(&joined, " : ")
+    } else {
+        Team::all(3)
+            .iter()
+            .filter_map(|t| {
+                let members: Vec<String> = teams
+                    .iter()
+                    .filter(|(_, pt)| pt == t)
+                    .map(|(p, _)| names.get(*p))
+                    .collect();
+                (!members.is_empty()).then(|| format!("{}: {}", t.name(), members.join(", ")))
+            })
+            .collect::<Vec<String>>()
+            .join(" - ")
+    };
+    let free = joined.len() < MAX_PLAYERS;
     rsx! {
         div {
             class: "centered-div",
 
             "En attente d'autres joueurs - {colors_str}"
+            if free {
+                br{}
+                for difficulty in Difficulty::all() {
+                    button {onclick: move |_| async move {
+                        if let Err(e) = snake_add_bot(difficulty).await {
+                            tracing::error!("While adding bot: {e:?}");
+                        }
+                    },
+                        "+ Robot {difficulty}"
+                    }
+                }
+            }
         }
     }
 }
 
 #[component]
-fn WaitWinner() -> Element {
+fn WaitWinner(players: Vec<PlayColor>, names: Nicknames) -> Element {
     rsx! {
         div {
             class: "centered-div",
 
             "Un jeu est en cours - faut patienter!"
+            PlayerNames { players, names }
+        }
+    }
+}
+
+/// The nicknames of the players, each in their color.
+#[component]
+fn PlayerNames(players: Vec<PlayColor>, names: Nicknames) -> Element {
+    rsx! {
+        div {
+            for color in players {
+                div {
+                    style: "color: #{color.to_hex()};",
+                    "{names.get(color)}"
+                }
+            }
         }
     }
 }
 
 #[component]
-pub fn Play(players: Vec<PlayColor>, player: PlayColor) -> Element {
+pub fn Play(players: Vec<PlayColor>, player: PlayColor, session: String) -> Element {
+    let session = use_signal(|| session);
     use_effect(move || {
         document_eval(&[
             &format!("const LED_COUNT = {LED_COUNT};"),
             include_str!("../../play.js"),
-            &format!("playerLED('{}')", player),
+            &format!("playerLED('#{}')", player.to_hex()),
         ]);
     });
 
-    async fn turn(player: PlayColor, dir: Option<TurnDir>) {
-        if let Err(e) = snake_player_turn(player, dir).await {
-            tracing::error!("While calling player_turn: {e:?}");
+    let mut cooldown = use_signal(|| 0u64);
+
+    async fn turn(session: String, dir: Option<TurnDir>) {
+        match snake_player_turn(session, dir).await {
+            Ok(Limited::Accepted(_)) => {}
+            Ok(Limited::Cooldown(ms)) => tracing::warn!("Turning too fast, wait {ms}ms"),
+            Err(e) => tracing::error!("While calling player_turn: {e:?}"),
+        }
+    }
+
+    async fn fire(session: String, dir: TurnDir) {
+        match snake_player_fire(session, dir).await {
+            Ok(Limited::Accepted(_)) => {}
+            Ok(Limited::Cooldown(ms)) => tracing::warn!("Firing too fast, wait {ms}ms"),
+            Err(e) => tracing::error!("While calling player_fire: {e:?}"),
         }
     }
 
@@ -129,31 +310,59 @@ pub fn Play(players: Vec<PlayColor>, player: PlayColor) -> Element {
             style: "background-color: #{player.to_hex_pastel()};",
             div {
                 class: "snakeDivision",
-                onmousedown: move |_| async move {turn(player, Some(TurnDir::Left)).await},
-                onmouseout: move |_| async move {turn(player, None).await},
-                onmouseup: move |_| async move {turn(player, None).await},
+                onmousedown: move |_| async move {turn(session(), Some(TurnDir::Left)).await},
+                onmouseout: move |_| async move {turn(session(), None).await},
+                onmouseup: move |_| async move {turn(session(), None).await},
                 span {
                     class: "snakeSymbol",
                     "↺"
                 }
             }
             div {
-                class: "snakeDivision snakeJump",
-                onclick: move |_| async move {
-                    if let Err(e) = snake_player_jump(player).await{
-                        tracing::error!("While calling player_jump: {e:?}");
+                class: "snakeMiddle",
+                div {
+                    class: if cooldown() > 0 { "snakeDivision snakeJump cooldown" } else { "snakeDivision snakeJump" },
+                    onclick: move |_| async move {
+                        match snake_player_jump(session()).await {
+                            Ok(Limited::Accepted(_)) => {}
+                            Ok(Limited::Cooldown(ms)) => {
+                                cooldown.set(ms);
+                                sleep(Duration::from_millis(ms)).await;
+                                cooldown.set(0);
+                            }
+                            Err(e) => tracing::error!("While calling player_jump: {e:?}"),
+                        }
+                    },
+                    span {
+                        class: "snakeSymbol",
+                        "↑"
+                    }
+                }
+                div {
+                    class: "snakeFire",
+                    div {
+                        class: "snakeDivision",
+                        onclick: move |_| async move {fire(session(), TurnDir::Left).await},
+                        span {
+                            class: "snakeSymbol",
+                            "⇠"
+                        }
+                    }
+                    div {
+                        class: "snakeDivision",
+                        onclick: move |_| async move {fire(session(), TurnDir::Right).await},
+                        span {
+                            class: "snakeSymbol",
+                            "⇢"
+                        }
                     }
-                },
-                span {
-                    class: "snakeSymbol",
-                    "↑"
                 }
             }
             div {
                 class: "snakeDivision",
-                onmousedown: move |_| async move {turn(player, Some(TurnDir::Right)).await},
-                onmouseout: move |_| async move {turn(player, None).await},
-                onmouseup: move |_| async move {turn(player, None).await},
+                onmousedown: move |_| async move {turn(session(), Some(TurnDir::Right)).await},
+                onmouseout: move |_| async move {turn(session(), None).await},
+                onmouseup: move |_| async move {turn(session(), None).await},
                 span {
                     class: "snakeSymbol",
                     "↻"
@@ -164,7 +373,7 @@ pub fn Play(players: Vec<PlayColor>, player: PlayColor) -> Element {
 }
 
 #[component]
-fn Winner(winner: PlayColor, player: Option<PlayColor>) -> Element {
+pub fn Winner(winner: PlayColor, player: Option<PlayColor>, names: Nicknames) -> Element {
     use_effect(move || {
         if let Some(p) = player {
             if winner == p {
@@ -177,7 +386,7 @@ fn Winner(winner: PlayColor, player: Option<PlayColor>) -> Element {
         div {
             class: "centered-div",
 
-            "La couleur gagnante est {winner.to_string()}"
+            "{names.get(winner)} a gagné!"
             br{}
             br{}
             canvas {display: "block", id: "fireworks"}
@@ -194,18 +403,69 @@ fn Winner(winner: PlayColor, player: Option<PlayColor>) -> Element {
 }
 
 #[component]
-fn Draw() -> Element {
+fn TeamWinner(
+    team: Team,
+    members: Vec<PlayColor>,
+    player: Option<PlayColor>,
+    names: Nicknames,
+) -> Element {
+    let won = player.is_some_and(|p| members.contains(&p));
+    use_effect(move || {
+        if won {
+            document::eval(include_str!("../../fireworks.js"));
+        }
+    });
+
+    let members_str = names.join(&members, ", ");
+
+    rsx! {
+        div {
+            class: "centered-div",
+
+            "L'équipe gagnante est {team.name()}: {members_str}"
+            br{}
+            br{}
+            canvas {display: "block", id: "fireworks"}
+
+            if player.is_some() {
+                if won {
+                    "Toutes nos félicitations!"
+                } else {
+                    "Bonne chance la prochaine fois!"
+                }
+            }
+        }
+    }
+}
+
+/// The nicknames with the most wins since the server started.
+#[component]
+fn Leaderboard(leaderboard: Vec<(String, usize)>) -> Element {
+    rsx! {
+        div {
+            style: "position: absolute; bottom: 0; width: 100%; text-align: center;",
+            "Meilleurs joueurs: "
+            for (i, (name, wins)) in leaderboard.into_iter().enumerate() {
+                "{i + 1}. {name} ({wins}) "
+            }
+        }
+    }
+}
+
+#[component]
+fn Draw(players: Vec<PlayColor>, names: Nicknames) -> Element {
     rsx! {
         div {
             class: "centered-div",
 
             "C'était chaud - personne n'a gagné..."
+            PlayerNames { players, names }
         }
     }
 }
 
 #[server(endpoint = "snake/state")]
-async fn snake_state() -> Result<SnakeGame, ServerFnError> {
+async fn snake_state() -> Result<SnakeStatus, ServerFnError> {
     let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
     if let Some(AnswerSnake::State(state)) = plat.snake_message(MessagesSnake::GetState) {
         Ok(state)
@@ -214,30 +474,99 @@ async fn snake_state() -> Result<SnakeGame, ServerFnError> {
     }
 }
 
+/// Returns the color bound to the session, if it is still part of the game.
+#[server(endpoint = "snake/rejoin")]
+async fn snake_rejoin(session: String) -> Result<Option<PlayColor>, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if let Some(AnswerSnake::Player(player)) = plat.snake_message(MessagesSnake::Rejoin(session)) {
+        Ok(player)
+    } else {
+        Err(ServerFnError::ServerError("didn't get player".into()))
+    }
+}
+
+/// Returns the session controlling the player, if the join succeeded.
 #[server(endpoint = "snake/join")]
-async fn snake_join(c: PlayColor) -> Result<bool, ServerFnError> {
+async fn snake_join(c: PlayColor, name: String) -> Result<Limited<Option<String>>, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if let Err(wait) = plat.rate_limit(Game::Snake, &crate::client_ip().await?) {
+        return Ok(Limited::Cooldown(wait.as_millis() as u64));
+    }
+    if let Some(AnswerSnake::Joined(joined)) = plat.snake_message(MessagesSnake::Join(c, name)) {
+        Ok(Limited::Accepted(joined))
+    } else {
+        Err(ServerFnError::ServerError("didn't get join state".into()))
+    }
+}
+
+#[server(endpoint = "snake/join_team")]
+async fn snake_join_team(
+    c: PlayColor,
+    teams: usize,
+    team: Team,
+    name: String,
+) -> Result<Limited<Option<String>>, ServerFnError> {
     let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
-    if let Some(AnswerSnake::Joined(joined)) = plat.snake_message(MessagesSnake::Join(c)) {
-        Ok(joined)
+    if let Err(wait) = plat.rate_limit(Game::Snake, &crate::client_ip().await?) {
+        return Ok(Limited::Cooldown(wait.as_millis() as u64));
+    }
+    if let Some(AnswerSnake::Joined(joined)) =
+        plat.snake_message(MessagesSnake::JoinTeam(c, teams, team, name))
+    {
+        Ok(Limited::Accepted(joined))
     } else {
         Err(ServerFnError::ServerError("didn't get join state".into()))
     }
 }
 
+#[server(endpoint = "snake/add_bot")]
+async fn snake_add_bot(difficulty: Difficulty) -> Result<Limited<bool>, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if let Err(wait) = plat.rate_limit(Game::Snake, &crate::client_ip().await?) {
+        return Ok(Limited::Cooldown(wait.as_millis() as u64));
+    }
+    if let Some(AnswerSnake::BotAdded(added)) =
+        plat.snake_message(MessagesSnake::AddBot(difficulty))
+    {
+        Ok(Limited::Accepted(added))
+    } else {
+        Err(ServerFnError::ServerError("didn't get bot state".into()))
+    }
+}
+
 fn document_eval(parts: &[&str]) {
     document::eval(&parts.join("\n"));
 }
 
 #[server(endpoint = "snake/player_turn")]
-async fn snake_player_turn(player: PlayColor, dir: Option<TurnDir>) -> Result<(), ServerFnError> {
+async fn snake_player_turn(
+    session: String,
+    dir: Option<TurnDir>,
+) -> Result<Limited<()>, ServerFnError> {
     let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
-    plat.snake_message(MessagesSnake::PlayerTurn(player, dir));
-    Ok(())
+    if let Err(wait) = plat.rate_limit_session(Game::Snake, &session) {
+        return Ok(Limited::Cooldown(wait.as_millis() as u64));
+    }
+    plat.snake_message(MessagesSnake::PlayerTurn(session, dir));
+    Ok(Limited::Accepted(()))
+}
+
+#[server(endpoint = "snake/player_fire")]
+async fn snake_player_fire(session: String, dir: TurnDir) -> Result<Limited<()>, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if let Err(wait) = plat.rate_limit_session(Game::Snake, &session) {
+        return Ok(Limited::Cooldown(wait.as_millis() as u64));
+    }
+    plat.snake_message(MessagesSnake::PlayerFire(session, dir));
+    Ok(Limited::Accepted(()))
 }
 
 #[server(endpoint = "snake/player_jump")]
-async fn snake_player_jump(player: PlayColor) -> Result<(), ServerFnError> {
+async fn snake_player_jump(session: String) -> Result<Limited<()>, ServerFnError> {
     let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
-    plat.snake_message(MessagesSnake::PlayerJump(player));
-    Ok(())
+    if let Err(wait) = plat.rate_limit_session(Game::Snake, &session) {
+        return Ok(Limited::Cooldown(wait.as_millis() as u64));
+    }
+    plat.snake_message(MessagesSnake::PlayerJump(session));
+    Ok(Limited::Accepted(()))
 }
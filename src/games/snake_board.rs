@@ -1,11 +1,21 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
+use strum_macros::EnumString;
 
 use crate::{
-    common::{PlayColor, FREQUENCY, LED_COUNT},
-    display::{Blob, Display},
-    games::snake::SnakeGame,
+    common::{Nicknames, PlayColor, Team, FREQUENCY, LED_COUNT},
+    display::{Blob, Display, Layer},
+    games::{
+        snake::{SnakeGame, SnakeStatus},
+        snake_bot::{Bot, Difficulty},
+    },
+};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Instant,
 };
-use std::collections::HashMap;
 
 #[cfg(debug_assertions)]
 const COUNTDOWN_PLAY: usize = 2;
@@ -20,11 +30,34 @@ const LIFE_INIT: usize = 5;
 const PLAYER_SPEED: usize = 60 / FREQUENCY;
 const JUMP_DURATION: usize = 4 * FREQUENCY;
 const JUMP_COOLDOWN: usize = 8 * FREQUENCY;
+const HISTORY_LENGTH: usize = 20;
+const TEAM_SPACING: usize = 8;
+const LEADERBOARD_LENGTH: usize = 5;
+const SHIELD_DURATION: usize = 10 * FREQUENCY;
+const SPEED_DURATION: usize = 5 * FREQUENCY;
+const SPEED_STEPS: usize = 2;
+const INVERT_DURATION: usize = 5 * FREQUENCY;
+const FREEZE_DURATION: usize = 3 * FREQUENCY;
+/// How far the drops go before disappearing, in LEDs.
+const DROP_DISTANCE: f32 = (LED_COUNT * 20 / FREQUENCY) as f32;
+const WALL_WIDTH: f32 = 5.;
+const ZIGZAG_PERIOD: usize = 2 * FREQUENCY;
+const SPLIT_DISTANCE: f32 = 10.;
+/// After this many obstacle speed-ups, the obstacle mix reaches its end weights.
+const MIX_LEVELS: usize = 12;
+const FIRE_COOLDOWN: usize = 2 * FREQUENCY;
+/// In LEDs per tick.
+const PROJECTILE_SPEED: f32 = 100. / FREQUENCY as f32;
 
+/// The controls of the players carry the session returned when joining.
 pub enum MessagesSnake {
-    PlayerTurn(PlayColor, Option<TurnDir>),
-    PlayerJump(PlayColor),
-    Join(PlayColor),
+    PlayerTurn(String, Option<TurnDir>),
+    PlayerJump(String),
+    PlayerFire(String, TurnDir),
+    Rejoin(String),
+    Join(PlayColor, String),
+    JoinTeam(PlayColor, usize, Team, String),
+    AddBot(Difficulty),
     GetState,
     Tick,
 }
@@ -36,25 +69,65 @@ pub enum TurnDir {
 }
 
 pub enum AnswerSnake {
-    Joined(bool),
-    State(SnakeGame),
+    Joined(Option<String>),
+    Player(Option<PlayColor>),
+    BotAdded(bool),
+    State(SnakeStatus),
+}
+
+/// An input of a player, as stored in the `MatchLog`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum MatchInput {
+    Turn(PlayColor, Option<TurnDir>),
+    Jump(PlayColor),
+    Fire(PlayColor, TurnDir),
 }
 
+/// Everything needed to replay a match: the initial setup of the board,
+/// and the inputs of the players with the tick they happened in.
+/// The nicknames and the winners are added once the match is over.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchLog {
+    pub players: Vec<PlayColor>,
+    pub teams: Vec<(PlayColor, Team)>,
+    pub seed: u64,
+    pub mix: ObstacleMix,
+    pub inputs: Vec<(usize, MatchInput)>,
+    pub ticks: usize,
+    pub names: Nicknames,
+    pub winners: Vec<PlayColor>,
+}
+
+/// The logs of the last matches, shared between the games.
+pub type MatchHistory = Arc<Mutex<Vec<MatchLog>>>;
+
 #[derive(Debug)]
 pub struct PlatformSnake {
     display: Display,
     board: Option<Board>,
     game: SnakeGame,
     countdown: usize,
+    history: MatchHistory,
+    bots: Vec<Bot>,
+    sessions: HashMap<String, PlayColor>,
+    names: Nicknames,
+    last_input: Instant,
+    mix: ObstacleMix,
 }
 
 impl PlatformSnake {
-    pub fn new() -> Self {
+    pub fn new(history: MatchHistory, mix: ObstacleMix) -> Self {
         Self {
             display: Display::new(),
             board: None,
             game: SnakeGame::Idle,
             countdown: 0,
+            history,
+            bots: vec![],
+            sessions: HashMap::new(),
+            names: Nicknames::default(),
+            last_input: Instant::now(),
+            mix,
         }
     }
 
@@ -62,38 +135,167 @@ impl PlatformSnake {
         self.display.get_circle()
     }
 
+    /// Nobody signed up nor plays.
+    pub fn is_idle(&self) -> bool {
+        matches!(self.game, SnakeGame::Idle)
+    }
+
+    pub fn has_session(&self, session: &str) -> bool {
+        self.sessions.contains_key(session)
+    }
+
+    pub fn last_input(&self) -> Instant {
+        self.last_input
+    }
+
     pub fn message(&mut self, msg: MessagesSnake) -> Option<AnswerSnake> {
+        if !matches!(
+            msg,
+            MessagesSnake::Tick | MessagesSnake::GetState | MessagesSnake::Rejoin(_)
+        ) {
+            self.last_input = Instant::now();
+        }
         match msg {
-            MessagesSnake::PlayerTurn(player, dir) => self.player_turn(player, dir),
-            MessagesSnake::PlayerJump(play_color) => self.player_click(play_color),
-            MessagesSnake::Join(play_color) => return Some(self.game_join(play_color)),
-            MessagesSnake::GetState => return Some(AnswerSnake::State(self.game.clone())),
+            MessagesSnake::PlayerTurn(session, dir) => self.player_turn(session, dir),
+            MessagesSnake::PlayerJump(session) => self.player_click(session),
+            MessagesSnake::PlayerFire(session, dir) => {
+                if let (Some(c), Some(board)) = (self.sessions.get(&session), self.board.as_mut()) {
+                    board.input(MatchInput::Fire(*c, dir));
+                }
+            }
+            MessagesSnake::Rejoin(session) => {
+                return Some(AnswerSnake::Player(self.sessions.get(&session).copied()))
+            }
+            MessagesSnake::Join(play_color, name) => {
+                let joined = self.game_join(play_color);
+                return Some(AnswerSnake::Joined(
+                    joined.then(|| self.new_session(play_color, &name)),
+                ));
+            }
+            MessagesSnake::JoinTeam(play_color, teams, team, name) => {
+                let joined = self.game_join_team(play_color, teams, team);
+                return Some(AnswerSnake::Joined(
+                    joined.then(|| self.new_session(play_color, &name)),
+                ));
+            }
+            MessagesSnake::AddBot(difficulty) => {
+                return Some(AnswerSnake::BotAdded(self.add_bot(difficulty)))
+            }
+            MessagesSnake::GetState => {
+                return Some(AnswerSnake::State(SnakeStatus {
+                    game: self.game.clone(),
+                    names: self.names.clone(),
+                    leaderboard: self.leaderboard(),
+                }))
+            }
             MessagesSnake::Tick => self.tick(),
         }
         None
     }
 
-    fn player_turn(&mut self, c: PlayColor, d: Option<TurnDir>) {
-        self.board.as_mut().map(|b| b.player_turn(c, d));
+    /// Binds a new session to the color, so only this phone can control it,
+    /// and stores the nickname of the player.
+    fn new_session(&mut self, c: PlayColor, name: &str) -> String {
+        let session = format!("{:032x}", rand::random::<u128>());
+        self.sessions.insert(session.clone(), c);
+        self.names.set(c, name);
+        session
     }
 
-    fn player_click(&mut self, c: PlayColor) {
-        self.board.as_mut().map(|b| b.player_click(c));
+    /// Counts the wins of every nickname in the match history.
+    /// Players without a nickname are left out, else all players of a color would share their wins.
+    fn leaderboard(&self) -> Vec<(String, usize)> {
+        let mut wins: Vec<(String, usize)> = vec![];
+        for log in self.history.lock().unwrap().iter() {
+            for winner in &log.winners {
+                let Some(name) = log.names.chosen(*winner) else {
+                    continue;
+                };
+                match wins.iter_mut().find(|(n, _)| n == &name) {
+                    Some((_, count)) => *count += 1,
+                    None => wins.push((name, 1)),
+                }
+            }
+        }
+        wins.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        wins.truncate(LEADERBOARD_LENGTH);
+        wins
+    }
+
+    fn player_turn(&mut self, session: String, d: Option<TurnDir>) {
+        if let (Some(c), Some(board)) = (self.sessions.get(&session), self.board.as_mut()) {
+            board.input(MatchInput::Turn(*c, d));
+        }
+    }
+
+    fn player_click(&mut self, session: String) {
+        if let (Some(c), Some(board)) = (self.sessions.get(&session), self.board.as_mut()) {
+            board.input(MatchInput::Jump(*c));
+        }
     }
 
-    fn game_join(&mut self, c: PlayColor) -> AnswerSnake {
+    /// Only colors which can be told apart from the other players can join.
+    fn game_join(&mut self, c: PlayColor) -> bool {
         match self.game.clone() {
-            SnakeGame::Idle => self.game = SnakeGame::Signup(vec![c]),
+            SnakeGame::Idle if c.can_join(&[]) => self.game = SnakeGame::Signup(vec![c]),
             SnakeGame::Signup(vec) => {
-                if vec.contains(&c) {
-                    return AnswerSnake::Joined(false);
+                if !c.can_join(&vec) {
+                    return false;
                 }
-                self.game = SnakeGame::Signup(vec![vec, vec![c]].concat());
+                self.game = SnakeGame::Signup([vec, vec![c]].concat());
                 self.countdown = COUNTDOWN_PLAY;
             }
-            _ => {}
+            _ => return false,
+        }
+        true
+    }
+
+    /// Joins the team mode with `teams` teams. The first player chooses the number of teams.
+    fn game_join_team(&mut self, c: PlayColor, teams: usize, team: Team) -> bool {
+        match self.game.clone() {
+            SnakeGame::Idle => {
+                let teams = teams.clamp(2, 3);
+                if !c.can_join(&[]) || !Team::all(teams).contains(&team) {
+                    return false;
+                }
+                self.game = SnakeGame::TeamSignup(teams, vec![(c, team)]);
+            }
+            SnakeGame::TeamSignup(teams, joined) => {
+                let players: Vec<PlayColor> = joined.iter().map(|(p, _)| *p).collect();
+                if !c.can_join(&players) || !Team::all(teams).contains(&team) {
+                    return false;
+                }
+                self.game = SnakeGame::TeamSignup(teams, [joined, vec![(c, team)]].concat());
+                self.countdown = COUNTDOWN_PLAY;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Adds a bot with the first free color, if signup is running.
+    /// In team mode, the bot joins the team with the fewest players.
+    fn add_bot(&mut self, difficulty: Difficulty) -> bool {
+        let players = match &self.game {
+            SnakeGame::Signup(players) => players.clone(),
+            SnakeGame::TeamSignup(_, joined) => joined.iter().map(|(p, _)| *p).collect(),
+            _ => return false,
+        };
+        let Some(color) = PlayColor::all().into_iter().find(|c| c.can_join(&players)) else {
+            return false;
+        };
+        self.bots.push(Bot::new(color, difficulty));
+        self.names.set(color, &format!("Robot {difficulty}"));
+        match self.game.clone() {
+            SnakeGame::TeamSignup(teams, joined) => {
+                let team = Team::all(teams)
+                    .into_iter()
+                    .min_by_key(|t| joined.iter().filter(|(_, jt)| jt == t).count())
+                    .unwrap_or(Team::One);
+                self.game_join_team(color, teams, team)
+            }
+            _ => self.game_join(color),
         }
-        AnswerSnake::Joined(true)
     }
 
     fn tick(&mut self) {
@@ -107,19 +309,52 @@ impl PlatformSnake {
                 }
                 self.display.game_signup(players, self.countdown);
             }
+            SnakeGame::TeamSignup(teams, joined) => {
+                let team_count = joined.iter().map(|(_, t)| t).collect::<HashSet<_>>().len();
+                if team_count < 2 {
+                    self.countdown = COUNTDOWN_PLAY;
+                }
+                self.display
+                    .game_signup_teams(teams, joined, self.countdown);
+            }
             SnakeGame::Play(_) => {
                 self.display.flow();
                 if let Some(board) = self.board.as_mut() {
+                    for bot in &mut self.bots {
+                        bot.decide(board)
+                            .into_iter()
+                            .for_each(|input| board.input(input));
+                    }
                     self.game = board.tick(&mut self.display);
-                    if matches!(self.game, SnakeGame::Winner(_)) {
+                    if matches!(
+                        self.game,
+                        SnakeGame::Winner(_) | SnakeGame::TeamWinner(_, _)
+                    ) {
                         self.countdown = COUNTDOWN_WINNER;
                     }
+                    if !matches!(self.game, SnakeGame::Play(_)) {
+                        let mut log = board.log.clone();
+                        log.names = self.names.clone();
+                        log.winners = match &self.game {
+                            SnakeGame::Winner(winner) => vec![*winner],
+                            SnakeGame::TeamWinner(_, members) => members.clone(),
+                            _ => vec![],
+                        };
+                        let mut history = self.history.lock().unwrap();
+                        history.push(log);
+                        if history.len() > HISTORY_LENGTH {
+                            history.remove(0);
+                        }
+                    }
                 }
             }
             SnakeGame::Winner(winner) => {
                 self.display.game_winner(winner, self.countdown);
             }
-            SnakeGame::Draw => {
+            SnakeGame::TeamWinner(team, _) => {
+                self.display.game_winner(team, self.countdown);
+            }
+            SnakeGame::Draw(_) => {
                 self.display.game_draw(self.countdown);
             }
         }
@@ -129,43 +364,156 @@ impl PlatformSnake {
             if self.countdown == 0 {
                 self.game = match self.game.clone() {
                     SnakeGame::Signup(players) => {
-                        self.board = Some(Board::new(players.clone()));
+                        self.board = Some(Board::new(
+                            players.clone(),
+                            vec![],
+                            rand::random(),
+                            self.mix.clone(),
+                        ));
+                        self.display.reset();
+                        SnakeGame::Play(players)
+                    }
+                    SnakeGame::TeamSignup(_, joined) => {
+                        let players: Vec<PlayColor> = joined.iter().map(|(p, _)| *p).collect();
+                        self.board = Some(Board::new(
+                            players.clone(),
+                            joined,
+                            rand::random(),
+                            self.mix.clone(),
+                        ));
                         self.display.reset();
                         SnakeGame::Play(players)
                     }
-                    _ => SnakeGame::Idle,
+                    _ => {
+                        self.bots.clear();
+                        self.sessions.clear();
+                        self.names = Nicknames::default();
+                        SnakeGame::Idle
+                    }
                 }
             }
         }
+        self.display.flatten();
         // tracing::debug!("New game state is: {:?} - {}", self.game, self.countdown);
     }
 }
 
+/// All randomness comes from the seeded `rng`, so a board replays the same match
+/// when given the same seed and inputs.
+/// If `teams` is empty, every player is on their own.
 #[derive(Debug)]
 pub struct Board {
     players: HashMap<PlayColor, Player>,
     obstacles: Vec<Drop>,
-    boni: Vec<Drop>,
+    boni: Vec<Bonus>,
+    projectiles: Vec<Projectile>,
     obstacle: usize,
+    frozen: usize,
+    rng: StdRng,
+    log: MatchLog,
 }
 
 impl Board {
-    pub fn new(player_colors: Vec<PlayColor>) -> Self {
-        let players = player_colors
-            .iter()
-            .enumerate()
-            .map(|(i, p)| {
-                (
-                    *p,
-                    Player::new(i * LED_COUNT / player_colors.len(), *p, LIFE_INIT),
-                )
-            })
-            .collect::<HashMap<_, _>>();
+    pub fn new(
+        player_colors: Vec<PlayColor>,
+        teams: Vec<(PlayColor, Team)>,
+        seed: u64,
+        mix: ObstacleMix,
+    ) -> Self {
+        let players = if teams.is_empty() {
+            player_colors
+                .iter()
+                .enumerate()
+                .map(|(i, p)| {
+                    (
+                        *p,
+                        Player::new(i * LED_COUNT / player_colors.len(), *p, None, LIFE_INIT),
+                    )
+                })
+                .collect::<HashMap<_, _>>()
+        } else {
+            Self::team_players(&teams)
+        };
         Self {
             players,
             obstacles: vec![],
             boni: vec![],
+            projectiles: vec![],
             obstacle: OBSTACLE_INTERVAL * 20,
+            frozen: 0,
+            rng: StdRng::seed_from_u64(seed),
+            log: MatchLog {
+                players: player_colors,
+                teams,
+                seed,
+                mix,
+                inputs: vec![],
+                ticks: 0,
+                names: Nicknames::default(),
+                winners: vec![],
+            },
+        }
+    }
+
+    /// Spreads the teams equally around the circle, with the teammates next to each other.
+    fn team_players(teams: &[(PlayColor, Team)]) -> HashMap<PlayColor, Player> {
+        let present: Vec<Team> = Team::all(3)
+            .into_iter()
+            .filter(|t| teams.iter().any(|(_, pt)| pt == t))
+            .collect();
+        let count = present.len();
+        present
+            .iter()
+            .enumerate()
+            .flat_map(|(i, team)| {
+                teams
+                    .iter()
+                    .filter(move |(_, t)| t == team)
+                    .enumerate()
+                    .map(move |(j, (p, t))| {
+                        let pos = i * LED_COUNT / count + j * TEAM_SPACING;
+                        (*p, Player::new(pos % LED_COUNT, *p, Some(*t), LIFE_INIT))
+                    })
+            })
+            .collect()
+    }
+
+    /// Creates a board in the initial state of the logged match.
+    pub fn from_log(log: &MatchLog) -> Self {
+        Self::new(
+            log.players.clone(),
+            log.teams.clone(),
+            log.seed,
+            log.mix.clone(),
+        )
+    }
+
+    pub fn ticks(&self) -> usize {
+        self.log.ticks
+    }
+
+    pub fn player(&self, c: PlayColor) -> Option<&Player> {
+        self.players.get(&c)
+    }
+
+    pub fn obstacles(&self) -> &[Drop] {
+        &self.obstacles
+    }
+
+    pub fn boni(&self) -> &[Bonus] {
+        &self.boni
+    }
+
+    pub fn projectiles(&self) -> &[Projectile] {
+        &self.projectiles
+    }
+
+    pub fn input(&mut self, input: MatchInput) {
+        self.log.inputs.push((self.log.ticks, input.clone()));
+        match input {
+            MatchInput::Turn(c, d) => self.player_turn(c, d),
+            MatchInput::Jump(c) => self.player_click(c),
+            MatchInput::Fire(c, dir) => self.player_fire(c, dir),
         }
     }
 
@@ -181,24 +529,51 @@ impl Board {
         }
     }
 
+    /// The player spends a life to send a projectile to the opponents.
+    pub fn player_fire(&mut self, c: PlayColor, dir: TurnDir) {
+        if let Some(player) = self.players.get_mut(&c) {
+            if player.fire() {
+                self.projectiles.push(Projectile {
+                    drop: Drop::shot(player.pos, &dir),
+                    color: c,
+                    team: player.team,
+                });
+            }
+        }
+    }
+
     pub fn tick(&mut self, display: &mut Display) -> SnakeGame {
-        self.obstacles.retain_mut(|o| o.tick_visible());
-        self.boni.retain_mut(|b| b.tick_visible());
+        self.log.ticks += 1;
+        if self.frozen > 0 {
+            self.frozen -= 1;
+        } else {
+            self.obstacles.retain_mut(|o| o.tick_visible());
+        }
+        self.boni.retain_mut(|b| b.drop.tick_visible());
+        self.projectiles.retain_mut(|p| p.drop.tick_visible());
+        self.split_obstacles();
         self.check_collision(vec![]);
 
-        // Only check collisions for players who moved.
+        self.players.values_mut().for_each(|p| p.prev = p.pos);
+        // Only check collisions for players who moved, after every single step,
+        // so fast players don't pass through obstacles or over pickups.
         for _ in 0..PLAYER_SPEED {
-            let positions: Vec<Player> = self.players.values().cloned().collect();
-            let players_ignore: Vec<PlayColor> = self
-                .players
-                .iter_mut()
-                .filter_map(|(color, player)| {
-                    let orig = player.pos;
-                    player.tick(positions.clone());
-                    (player.pos == orig).then(|| *color)
-                })
-                .collect();
-            self.check_collision(players_ignore);
+            for step in 0..SPEED_STEPS {
+                let positions: Vec<Player> = self.players.values().cloned().collect();
+                let players_ignore: Vec<PlayColor> = self
+                    .players
+                    .iter_mut()
+                    .filter_map(|(color, player)| {
+                        let orig = player.pos;
+                        if step < player.steps() {
+                            player.step(&positions);
+                        }
+                        (player.pos == orig).then_some(*color)
+                    })
+                    .collect();
+                self.check_collision(players_ignore);
+            }
+            self.players.values_mut().for_each(|p| p.tick());
         }
 
         let speed_up = display.counter % (FREQUENCY * OBSTACLE_INCREASE_SEC);
@@ -206,108 +581,352 @@ impl Board {
             self.obstacle = self.obstacle * 2 / 3;
         }
 
-        if rand::random::<f32>() < 1. / (self.obstacle as f32 / 10.0) {
-            self.obstacles.push(Drop::rand());
+        if self.rng.gen::<f32>() < 1. / (self.obstacle as f32 / 10.0) && self.frozen == 0 {
+            let level = self.log.ticks / (FREQUENCY * OBSTACLE_INCREASE_SEC);
+            let difficulty = (level as f32 / MIX_LEVELS as f32).min(1.);
+            let kind = self.log.mix.choose(difficulty, &mut self.rng);
+            self.obstacles.push(Drop::new(kind, &mut self.rng));
         }
-        if rand::random::<f32>() < 1. / BONUS_INTERVAL as f32 {
-            self.boni.push(Drop::rand());
+        if self.rng.gen::<f32>() < 1. / BONUS_INTERVAL as f32 {
+            self.boni.push(Bonus {
+                drop: Drop::rand(&mut self.rng),
+                pickup: Pickup::rand(&mut self.rng),
+            });
         }
 
         display.clear();
         display.draw_blobs(
-            self.players
-                .values()
-                .cloned()
-                .map(|p| Blob::Player(p))
+            Layer::Entities,
+            self.players.values().cloned().map(Blob::Player).collect(),
+        );
+        display.draw_blobs(
+            Layer::Entities,
+            self.obstacles.iter().map(|o| o.obstacle()).collect(),
+        );
+        display.draw_blobs(
+            Layer::Entities,
+            self.boni
+                .iter()
+                .map(|b| Blob::Pickup(b.drop.pos_smooth(), b.pickup))
+                .collect(),
+        );
+        display.draw_blobs(
+            Layer::Entities,
+            self.projectiles
+                .iter()
+                .map(|p| Blob::Projectile(p.drop.pos_smooth(), p.color, p.drop.speed()))
                 .collect(),
         );
-        display.draw_blobs(self.obstacles.iter().map(|o| o.obstacle()).collect());
-        display.draw_blobs(self.boni.iter().map(|b| b.bonus()).collect());
         if speed_up < FREQUENCY {
             display.shine(speed_up as f32 / FREQUENCY as f32 * 2.0);
         }
+        if self.frozen > 0 {
+            display.tint(Pickup::Freeze.into());
+        }
 
-        if self.players.len() > 1 {
+        if !self.log.teams.is_empty() {
+            let teams: HashSet<Team> = self.players.values().filter_map(|p| p.team).collect();
+            if teams.len() > 1 {
+                SnakeGame::Play(self.players.keys().cloned().collect())
+            } else if let Some(team) = teams.into_iter().next() {
+                let members = self
+                    .log
+                    .teams
+                    .iter()
+                    .filter(|(_, t)| t == &team)
+                    .map(|(p, _)| *p)
+                    .collect();
+                SnakeGame::TeamWinner(team, members)
+            } else {
+                SnakeGame::Draw(self.log.players.clone())
+            }
+        } else if self.players.len() > 1 {
             SnakeGame::Play(self.players.keys().cloned().collect())
         } else if let Some(winner) = self.players.iter().next() {
             SnakeGame::Winner(*winner.0)
         } else {
-            SnakeGame::Draw
+            SnakeGame::Draw(self.log.players.clone())
         }
     }
 
+    fn split_obstacles(&mut self) {
+        let positions: Vec<Position> = self.players.values().map(|p| p.pos).collect();
+        self.obstacles = self
+            .obstacles
+            .iter()
+            .flat_map(|o| o.split(&positions).map_or(vec![*o], |parts| parts.to_vec()))
+            .collect();
+    }
+
     fn check_collision(&mut self, players_ignore: Vec<PlayColor>) {
         self.players.retain(|_, p: &mut Player| p.lifes > 0);
+        let mut collected = vec![];
         for (_, player) in self.players.iter_mut() {
             if player.jump == 0 && !players_ignore.contains(&player.color) {
                 for o in &mut self.obstacles {
-                    if o.pos() == player.pos {
-                        player.lifes -= 1;
+                    if o.hits(player.pos) {
+                        player.hit();
                         o.clear = true;
                     }
                 }
 
                 for b in &mut self.boni {
-                    if b.pos() == player.pos {
-                        player.lifes += 1;
-                        b.clear = true;
+                    if b.drop.pos() == player.pos {
+                        collected.push((player.color, b.pickup));
+                        b.drop.clear = true;
                     }
                 }
             }
         }
+        for (color, pickup) in collected {
+            self.collect(color, pickup);
+        }
+        for p in &mut self.projectiles {
+            // The hash map has no fixed order, so the first target is chosen
+            // explicitly for replays to give the same result.
+            let reached = |t: &Player| {
+                let dist = Position::distances(t.pos.0, p.drop.pos().0);
+                (dist * p.drop.speed().signum() as i32, t.color.to_hex())
+            };
+            if let Some(target) = self
+                .players
+                .values_mut()
+                .filter(|t| t.jump == 0 && !p.spares(t) && p.drop.hits(t.pos))
+                .min_by_key(|t| reached(t))
+            {
+                target.hit();
+                p.drop.clear = true;
+            }
+        }
+        self.projectiles.retain(|p| !p.drop.clear);
         self.obstacles.retain(|o| !o.clear);
-        self.boni.retain(|b| !b.clear);
+        self.boni.retain(|b| !b.drop.clear);
+    }
+
+    /// Applies the pickup collected by the player.
+    fn collect(&mut self, color: PlayColor, pickup: Pickup) {
+        let Some(team) = self.players.get(&color).map(|p| p.team) else {
+            return;
+        };
+        match pickup {
+            Pickup::Freeze => self.frozen = FREEZE_DURATION,
+            Pickup::Invert => self
+                .players
+                .values_mut()
+                .filter(|p| p.color != color && (p.team.is_none() || p.team != team))
+                .for_each(|p| p.add_effect(Pickup::Invert, INVERT_DURATION)),
+            _ => {
+                if let Some(player) = self.players.get_mut(&color) {
+                    player.collect(pickup);
+                }
+            }
+        }
+    }
+}
+
+/// What the boni give to the player collecting them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Pickup {
+    /// One more life.
+    Life,
+    /// Absorbs the next hit of an obstacle.
+    Shield,
+    /// Moves twice as fast.
+    Speed,
+    /// Inverts the controls of the opponents.
+    Invert,
+    /// Stops all obstacles.
+    Freeze,
+    /// The player can jump again immediately.
+    JumpRefill,
+}
+
+impl Pickup {
+    pub fn all() -> Vec<Pickup> {
+        vec![
+            Self::Life,
+            Self::Shield,
+            Self::Speed,
+            Self::Invert,
+            Self::Freeze,
+            Self::JumpRefill,
+        ]
+    }
+
+    /// How often the pickup appears, relative to the others.
+    fn weight(&self) -> u32 {
+        match self {
+            Pickup::Life => 4,
+            Pickup::Shield | Pickup::Speed | Pickup::JumpRefill => 2,
+            Pickup::Invert | Pickup::Freeze => 1,
+        }
+    }
+
+    fn rand(rng: &mut StdRng) -> Self {
+        let all = Self::all();
+        let mut choice = rng.gen_range(0..all.iter().map(|p| p.weight()).sum::<u32>());
+        for pickup in all {
+            if choice < pickup.weight() {
+                return pickup;
+            }
+            choice -= pickup.weight();
+        }
+        Pickup::Life
+    }
+
+    pub fn to_hex(self) -> String {
+        match self {
+            Pickup::Life => "22ff22",
+            Pickup::Shield => "2266ff",
+            Pickup::Speed => "ffdd00",
+            Pickup::Invert => "ff22ff",
+            Pickup::Freeze => "88ffff",
+            Pickup::JumpRefill => "ff8800",
+        }
+        .into()
+    }
+}
+
+/// A pickup going around the circle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bonus {
+    pub drop: Drop,
+    pub pickup: Pickup,
+}
+
+/// A projectile fired by a player, hitting the first opponent it reaches.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Projectile {
+    pub drop: Drop,
+    pub color: PlayColor,
+    pub team: Option<Team>,
+}
+
+impl Projectile {
+    /// The shooter and their teammates are not hit.
+    pub fn spares(&self, player: &Player) -> bool {
+        player.color == self.color || (player.team.is_some() && player.team == self.team)
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Player {
     pub pos: Position,
+    /// The position at the previous tick, to draw the player in between.
+    prev: Position,
     turn: Option<TurnDir>,
     pub color: PlayColor,
+    pub team: Option<Team>,
     pub lifes: usize,
     pub jump: usize,
     pub jump_recover: usize,
+    pub fire_recover: usize,
+    /// The running effects of the pickups, with their remaining ticks.
+    effects: Vec<(Pickup, usize)>,
 }
 
 impl Player {
-    fn new(pos: usize, color: PlayColor, lifes: usize) -> Self {
+    fn new(pos: usize, color: PlayColor, team: Option<Team>, lifes: usize) -> Self {
         let pos = Position(pos);
         Self {
             pos,
+            prev: pos,
             turn: None,
             color,
+            team,
             lifes,
             jump: 0,
             jump_recover: 0,
+            fire_recover: 0,
+            effects: vec![],
         }
     }
 
+    pub fn has(&self, effect: Pickup) -> bool {
+        self.effects.iter().any(|(e, _)| e == &effect)
+    }
+
+    fn add_effect(&mut self, effect: Pickup, ticks: usize) {
+        self.effects.retain(|(e, _)| e != &effect);
+        self.effects.push((effect, ticks));
+    }
+
+    fn collect(&mut self, pickup: Pickup) {
+        match pickup {
+            Pickup::Life => self.lifes += 1,
+            Pickup::JumpRefill => self.jump_recover = 0,
+            Pickup::Shield => self.add_effect(pickup, SHIELD_DURATION),
+            Pickup::Speed => self.add_effect(pickup, SPEED_DURATION),
+            Pickup::Invert | Pickup::Freeze => {}
+        }
+    }
+
+    /// The shield absorbs the hit, else the player loses a life.
+    fn hit(&mut self) {
+        if self.has(Pickup::Shield) {
+            self.effects.retain(|(e, _)| e != &Pickup::Shield);
+        } else {
+            self.lifes = self.lifes.saturating_sub(1);
+        }
+    }
+
+    /// Halfway between the previous and the current position, so a moving player
+    /// is spread over two LEDs instead of jumping from one to the next.
+    pub fn pos_smooth(&self) -> f32 {
+        let half = self.pos.direction(self.prev) as f32 / 2.;
+        (self.prev.0 as f32 + half).rem_euclid(LED_COUNT as f32)
+    }
+
     fn set_turn(&mut self, dir: Option<TurnDir>) {
         self.turn = dir;
     }
 
-    fn tick(&mut self, players: Vec<Player>) {
-        if let Some(dir) = self.turn.as_ref() {
-            let others: Vec<&Player> = players.iter().filter(|p| p.color != self.color).collect();
-            let new_pos = if dir == &TurnDir::Left {
-                self.pos.add(-1)
-            } else {
-                self.pos.add(1)
-            };
-            if others
-                .iter()
-                .all(|o| o.pos.direction(new_pos).abs() as usize >= (o.lifes + self.lifes) * 3 / 2)
-            {
-                self.pos = new_pos;
-            }
+    /// The number of LEDs the player moves per tick.
+    fn steps(&self) -> usize {
+        if self.has(Pickup::Speed) {
+            SPEED_STEPS
+        } else {
+            1
         }
+    }
+
+    /// Moves the player by one LED in the direction it turns, unless another player is in the way.
+    fn step(&mut self, players: &[Player]) {
+        let Some(dir) = self.turn.as_ref() else {
+            return;
+        };
+        let step = if (dir == &TurnDir::Left) != self.has(Pickup::Invert) {
+            -1
+        } else {
+            1
+        };
+        let new_pos = self.pos.add(step);
+        // Teammates can pass through each other.
+        if players
+            .iter()
+            .filter(|p| p.color != self.color && (p.team.is_none() || p.team != self.team))
+            .all(|o| {
+                o.pos.direction(new_pos).unsigned_abs() as usize >= (o.lifes + self.lifes) * 3 / 2
+            })
+        {
+            self.pos = new_pos;
+        }
+    }
+
+    fn tick(&mut self) {
+        self.effects.retain_mut(|(_, ticks)| {
+            *ticks -= 1;
+            *ticks > 0
+        });
         if self.jump > 0 {
             self.jump -= 1;
         }
         if self.jump_recover > 0 {
             self.jump_recover -= 1;
         }
+        if self.fire_recover > 0 {
+            self.fire_recover -= 1;
+        }
     }
 
     fn jump(&mut self) {
@@ -316,6 +935,17 @@ impl Player {
             self.jump_recover = JUMP_DURATION + JUMP_COOLDOWN;
         }
     }
+
+    /// Firing costs a life, but a player cannot spend their last one.
+    fn fire(&mut self) -> bool {
+        if self.lifes > 1 && self.fire_recover == 0 {
+            self.lifes -= 1;
+            self.fire_recover = FIRE_COOLDOWN;
+            true
+        } else {
+            false
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -339,48 +969,299 @@ impl Position {
         let d = delta.rem_euclid(LED_COUNT as i32) as usize;
         Self((self.0 + d) % LED_COUNT)
     }
+}
 
-    pub fn sub(&self, delta: i32) -> Self {
-        self.add(-delta)
+/// The kinds of obstacles, which differ in speed, size and movement.
+#[derive(EnumString, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[strum(ascii_case_insensitive)]
+pub enum ObstacleKind {
+    Normal,
+    /// Fast and thin.
+    Dart,
+    /// Slow and several LEDs wide.
+    Wall,
+    /// Changes its direction regularly.
+    Zigzag,
+    /// Splits in two when getting close to a player.
+    Splitter,
+}
+
+impl ObstacleKind {
+    /// The speed in LEDs per tick.
+    fn speed(&self) -> f32 {
+        let normal = 20. / FREQUENCY as f32;
+        match self {
+            ObstacleKind::Dart => normal * 3.,
+            ObstacleKind::Wall => normal / 2.,
+            _ => normal,
+        }
+    }
+
+    fn width(&self) -> f32 {
+        match self {
+            ObstacleKind::Wall => WALL_WIDTH,
+            _ => 1.,
+        }
+    }
+}
+
+/// The probabilities of the obstacle kinds, shifting from the first weight
+/// at the start of a match to the second weight once the obstacles are the fastest.
+/// It is written as `<kind>:<start>><end>` separated by commas, e.g. `Normal:8>2,Dart:0>3`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObstacleMix(Vec<(ObstacleKind, f32, f32)>);
+
+impl Default for ObstacleMix {
+    fn default() -> Self {
+        Self(vec![
+            (ObstacleKind::Normal, 8., 2.),
+            (ObstacleKind::Dart, 0., 3.),
+            (ObstacleKind::Wall, 1., 2.),
+            (ObstacleKind::Zigzag, 1., 2.),
+            (ObstacleKind::Splitter, 0., 1.),
+        ])
+    }
+}
+
+impl ObstacleMix {
+    /// Chooses a kind for the given difficulty between 0 and 1.
+    fn choose(&self, difficulty: f32, rng: &mut StdRng) -> ObstacleKind {
+        let weights: Vec<(ObstacleKind, f32)> = self
+            .0
+            .iter()
+            .map(|(kind, start, end)| (*kind, (start + (end - start) * difficulty).max(0.)))
+            .collect();
+        let mut choice = rng.gen::<f32>() * weights.iter().map(|(_, w)| w).sum::<f32>();
+        for (kind, weight) in weights {
+            if choice < weight {
+                return kind;
+            }
+            choice -= weight;
+        }
+        ObstacleKind::Normal
     }
 }
 
+impl FromStr for ObstacleMix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(|entry| {
+                let (kind, weights) = entry
+                    .split_once(':')
+                    .ok_or(format!("'{entry}' needs a kind and weights"))?;
+                let (start, end) = weights.split_once('>').unwrap_or((weights, weights));
+                let weight = |w: &str| w.trim().parse::<f32>().map_err(|e| format!("'{w}': {e}"));
+                Ok((
+                    kind.trim()
+                        .parse()
+                        .map_err(|_| format!("unknown obstacle {kind}"))?,
+                    weight(start)?,
+                    weight(end)?,
+                ))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+/// Something going around the circle for a limited time: an obstacle, or the pickup of a bonus.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Drop {
-    init: Position,
-    counter: i32,
-    direction: i32,
+    pos: f32,
+    speed: f32,
+    width: f32,
+    life: usize,
+    kind: ObstacleKind,
+    /// Ticks until a zigzag changes its direction.
+    turn: usize,
     clear: bool,
 }
 
 impl Drop {
-    pub fn rand() -> Self {
+    pub fn rand(rng: &mut StdRng) -> Self {
+        Self::new(ObstacleKind::Normal, rng)
+    }
+
+    /// All kinds travel the same distance before disappearing.
+    fn new(kind: ObstacleKind, rng: &mut StdRng) -> Self {
+        let pos = (rng.gen::<usize>() % LED_COUNT) as f32;
+        let direction = if rng.gen() { -1. } else { 1. };
+        let speed = kind.speed();
+        Self {
+            pos,
+            speed: speed * direction,
+            width: kind.width(),
+            life: (DROP_DISTANCE / speed) as usize,
+            kind,
+            turn: if kind == ObstacleKind::Zigzag {
+                rng.gen_range(ZIGZAG_PERIOD / 2..ZIGZAG_PERIOD * 2)
+            } else {
+                0
+            },
+            clear: false,
+        }
+    }
+
+    /// A projectile going once around the circle, starting next to the player.
+    fn shot(pos: Position, dir: &TurnDir) -> Self {
+        let direction = if dir == &TurnDir::Left { -1. } else { 1. };
         Self {
-            init: Position(rand::random::<usize>() % LED_COUNT),
-            counter: LED_COUNT as i32,
-            direction: if rand::random() { -1 } else { 1 },
+            pos: pos.0 as f32 + direction,
+            speed: PROJECTILE_SPEED * direction,
+            width: 1.,
+            life: (LED_COUNT as f32 / PROJECTILE_SPEED) as usize,
+            kind: ObstacleKind::Normal,
+            turn: 0,
             clear: false,
         }
     }
 
+    /// The signed movement in LEDs per tick.
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
     pub fn pos(&self) -> Position {
-        self.init
-            .add(self.counter * self.direction * 20 / FREQUENCY as i32)
+        Position((self.pos.round() as i32).rem_euclid(LED_COUNT as i32) as usize)
+    }
+
+    /// The position between two LEDs, for a smooth display.
+    pub fn pos_smooth(&self) -> f32 {
+        self.pos.rem_euclid(LED_COUNT as f32)
+    }
+
+    /// Wide or fast obstacles hit the player anywhere they cover, or passed over during the last tick.
+    fn hits(&self, pos: Position) -> bool {
+        let dist = (pos.0 as f32 - self.pos_smooth()).rem_euclid(LED_COUNT as f32);
+        dist.min(LED_COUNT as f32 - dist) <= (self.width + self.speed.abs()) / 2.
     }
 
     pub fn tick_visible(&mut self) -> bool {
-        if self.counter > 0 {
-            self.counter -= 1;
-            return true;
+        if self.life == 0 {
+            return false;
+        }
+        self.life -= 1;
+        self.pos += self.speed;
+        if self.kind == ObstacleKind::Zigzag {
+            self.turn -= 1;
+            if self.turn == 0 {
+                self.speed = -self.speed;
+                self.turn = ZIGZAG_PERIOD;
+            }
         }
-        false
+        true
     }
 
-    fn bonus(&self) -> Blob {
-        Blob::Bonus(self.pos())
+    /// A splitter close to a player becomes two normal obstacles: a fast one
+    /// going on, and one going back.
+    fn split(&self, players: &[Position]) -> Option<[Drop; 2]> {
+        let close = players.iter().any(|p| {
+            let dist = Position::distances(p.0, self.pos().0) as f32;
+            dist * self.speed > 0. && dist.abs() <= SPLIT_DISTANCE
+        });
+        (self.kind == ObstacleKind::Splitter && close).then(|| {
+            let mut on = *self;
+            on.kind = ObstacleKind::Normal;
+            on.speed *= 1.5;
+            let mut back = on;
+            back.speed = -self.speed;
+            [on, back]
+        })
     }
 
     fn obstacle(&self) -> Blob {
-        Blob::Obstacle(self.pos())
+        Blob::Obstacle(self.pos_smooth(), self.width, self.kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_team_joiner_needs_a_valid_team() {
+        let mut platform = PlatformSnake::new(MatchHistory::default(), ObstacleMix::default());
+        assert!(!platform.game_join_team(PlayColor::RED, 2, Team::Three));
+        assert!(matches!(platform.game, SnakeGame::Idle));
+        assert!(platform.game_join_team(PlayColor::RED, 5, Team::Three));
+        assert!(
+            matches!(&platform.game, SnakeGame::TeamSignup(3, joined) if joined == &[(PlayColor::RED, Team::Three)])
+        );
+    }
+
+    #[test]
+    fn moving_player_is_drawn_between_leds() {
+        let mut player = Player::new(0, PlayColor::RED, None, LIFE_INIT);
+        assert_eq!(player.pos_smooth(), 0.);
+        player.set_turn(Some(TurnDir::Left));
+        player.step(&[]);
+        assert_eq!(player.pos.0, LED_COUNT - 1);
+        assert_eq!(player.pos_smooth(), LED_COUNT as f32 - 0.5);
+    }
+
+    #[test]
+    fn leaderboard_leaves_out_players_without_nickname() {
+        let history = MatchHistory::default();
+        let platform = PlatformSnake::new(history.clone(), ObstacleMix::default());
+        let blue = PlayColor::rgb(0x00, 0x00, 0xff);
+        let mut log = Board::new(
+            vec![PlayColor::RED, blue],
+            vec![],
+            0,
+            ObstacleMix::default(),
+        )
+        .log;
+        log.names.set(PlayColor::RED, "Alice");
+        for winner in [PlayColor::RED, blue, blue] {
+            log.winners = vec![winner];
+            history.lock().unwrap().push(log.clone());
+        }
+        assert_eq!(platform.leaderboard(), vec![("Alice".to_string(), 1)]);
+    }
+
+    /// A single fast player at LED 0 running right, over a still thing at LED 1.
+    fn fast_player_board() -> Board {
+        let mut board = Board::new(vec![PlayColor::RED], vec![], 0, ObstacleMix::default());
+        let player = board.players.get_mut(&PlayColor::RED).unwrap();
+        player.add_effect(Pickup::Speed, SPEED_DURATION);
+        player.set_turn(Some(TurnDir::Right));
+        board
+    }
+
+    fn still(pos: f32) -> Drop {
+        Drop {
+            pos,
+            speed: 0.,
+            width: 1.,
+            life: FREQUENCY,
+            kind: ObstacleKind::Normal,
+            turn: 0,
+            clear: false,
+        }
+    }
+
+    #[test]
+    fn fast_player_collects_pickups_on_the_way() {
+        let mut board = fast_player_board();
+        board.boni.push(Bonus {
+            drop: still(1.),
+            pickup: Pickup::Life,
+        });
+        board.tick(&mut Display::new());
+        let player = &board.players[&PlayColor::RED];
+        assert_eq!(player.pos.0, 2);
+        assert_eq!(player.lifes, LIFE_INIT + 1);
+    }
+
+    #[test]
+    fn fast_player_hits_thin_obstacles() {
+        let mut board = fast_player_board();
+        board.obstacles.push(still(1.));
+        board.tick(&mut Display::new());
+        let player = &board.players[&PlayColor::RED];
+        assert_eq!(player.pos.0, 2);
+        assert_eq!(player.lifes, LIFE_INIT - 1);
     }
 }
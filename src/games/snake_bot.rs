@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+use crate::{
+    common::PlayColor,
+    games::snake_board::{Board, MatchInput, Position, TurnDir},
+};
+
+/// How well a bot plays.
+#[derive(Display, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn all() -> Vec<Difficulty> {
+        vec![Self::Easy, Self::Medium, Self::Hard]
+    }
+
+    /// Probability that the bot reacts in a given tick.
+    fn reaction(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.2,
+            Difficulty::Medium => 0.5,
+            Difficulty::Hard => 1.,
+        }
+    }
+
+    /// How many ticks in advance the bot sees an obstacle coming.
+    fn horizon(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 15.,
+            Difficulty::Medium => 40.,
+            Difficulty::Hard => 80.,
+        }
+    }
+
+    fn chases_boni(&self) -> bool {
+        self != &Difficulty::Easy
+    }
+}
+
+/// A computer-controlled player, which sends its inputs to the board like a phone would.
+#[derive(Debug)]
+pub struct Bot {
+    color: PlayColor,
+    difficulty: Difficulty,
+    turn: Option<TurnDir>,
+}
+
+impl Bot {
+    pub fn new(color: PlayColor, difficulty: Difficulty) -> Self {
+        Self {
+            color,
+            difficulty,
+            turn: None,
+        }
+    }
+
+    /// Returns the inputs of the bot for this tick.
+    /// Obstacles about to hit are jumped over if possible, else the bot runs away from them.
+    /// With no obstacle around, it goes for the nearest bonus.
+    pub fn decide(&mut self, board: &Board) -> Vec<MatchInput> {
+        let Some(player) = board.player(self.color) else {
+            return vec![];
+        };
+        if rand::random::<f32>() > self.difficulty.reaction() {
+            return vec![];
+        }
+
+        let projectiles = board
+            .projectiles()
+            .iter()
+            .filter(|p| !p.spares(player))
+            .map(|p| &p.drop);
+        let threat = board
+            .obstacles()
+            .iter()
+            .chain(projectiles)
+            .filter_map(|o| {
+                let dist = Position::distances(o.pos().0, player.pos.0) as f32;
+                let speed = o.speed();
+                (dist * speed < 0.).then(|| (dist.abs() / speed.abs(), speed))
+            })
+            .filter(|(time, _)| *time < self.difficulty.horizon())
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+
+        let turn = match threat {
+            Some((time, _)) if time < 5. && player.jump_recover == 0 => {
+                return vec![MatchInput::Jump(self.color)];
+            }
+            Some((_, speed)) => Some(if speed > 0. {
+                TurnDir::Right
+            } else {
+                TurnDir::Left
+            }),
+            None if self.difficulty.chases_boni() => board
+                .boni()
+                .iter()
+                .map(|b| Position::distances(b.drop.pos().0, player.pos.0))
+                .min_by_key(|dist| dist.abs())
+                .map(|dist| {
+                    if dist > 0 {
+                        TurnDir::Right
+                    } else {
+                        TurnDir::Left
+                    }
+                }),
+            None => None,
+        };
+
+        if turn != self.turn {
+            self.turn = turn.clone();
+            vec![MatchInput::Turn(self.color, turn)]
+        } else {
+            vec![]
+        }
+    }
+}
@@ -0,0 +1,607 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use async_std::task::sleep;
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{Game, Limited, Nicknames, PlayColor, FREQUENCY, LED_COUNT, MAX_PLAYERS},
+    display::{Blob, Display, Layer},
+    server, Route,
+};
+
+#[cfg(debug_assertions)]
+const COUNTDOWN_PLAY: usize = 2;
+#[cfg(not(debug_assertions))]
+const COUNTDOWN_PLAY: usize = LED_COUNT;
+
+const COUNTDOWN_RANKING: usize = 6 * FREQUENCY;
+const LIGHTS: usize = 20;
+const LIGHT_WIDTH: f32 = 12.;
+/// The window to tap starts at two seconds, and shrinks with every light.
+const WINDOW_START: usize = 2 * FREQUENCY;
+const WINDOW_SHRINK: f32 = 0.9;
+const WINDOW_MIN: usize = FREQUENCY / 3;
+const PAUSE_MIN: usize = FREQUENCY / 2;
+const PAUSE_MAX: usize = 2 * FREQUENCY;
+/// Taps arriving shortly after another phone took the light are late, not penalized.
+const TAP_GRACE: Duration = Duration::from_millis(300);
+const WHITE: PlayColor = PlayColor::rgb(0xff, 0xff, 0xff);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum WhackGame {
+    Idle,
+    Signup(Vec<PlayColor>),
+    Play(Vec<PlayColor>),
+    Ranking(Vec<PlayColor>),
+}
+
+/// The points of a player, and the reaction times of their hits in milliseconds.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WhackScore {
+    pub color: PlayColor,
+    pub points: i32,
+    pub reactions: Vec<u64>,
+}
+
+impl WhackScore {
+    fn new(color: PlayColor) -> Self {
+        Self {
+            color,
+            points: 0,
+            reactions: vec![],
+        }
+    }
+
+    fn best(&self) -> Option<u64> {
+        self.reactions.iter().min().copied()
+    }
+
+    fn average(&self) -> Option<u64> {
+        (!self.reactions.is_empty())
+            .then(|| self.reactions.iter().sum::<u64>() / self.reactions.len() as u64)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhackStatus {
+    pub game: WhackGame,
+    pub names: Nicknames,
+    pub scores: Vec<WhackScore>,
+}
+
+/// What happened to a tap: a hit with its reaction time, too late for a light
+/// taken by another phone, or a penalty for tapping on nothing.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum Whacked {
+    Hit(u64),
+    Late,
+    Penalty,
+}
+
+#[component]
+pub fn Whack() -> Element {
+    let mut whack = use_signal(|| WhackGame::Idle);
+    let mut names = use_signal(Nicknames::default);
+    let mut scores = use_signal(Vec::new);
+    let player: Signal<Option<PlayColor>> = use_signal(|| None);
+    let session: Signal<Option<String>> = use_signal(|| None);
+
+    use_future(move || async move {
+        loop {
+            match whack_state().await {
+                Ok(state) => {
+                    whack.set(state.game);
+                    names.set(state.names);
+                    scores.set(state.scores);
+                }
+                Err(e) => tracing::error!("While getting the whack state: {e:?}"),
+            }
+            sleep(Duration::from_millis(500)).await;
+        }
+    });
+
+    rsx! {
+        div {
+            Link {to: Route::Reset{}, style: "text-align: center; width: 100%;", "Home"}
+            match whack() {
+                WhackGame::Idle => rsx!{JoinWhack{joined: vec![], player, session}},
+                WhackGame::Signup(joined) => if player().is_some() {
+                    let waiting = names().join(&joined, " : ");
+                    rsx!{div {class: "centered-div", "En attente d'autres joueurs - {waiting}"}}
+                } else if joined.len() < MAX_PLAYERS {
+                    rsx!{JoinWhack{joined, player, session}}
+                } else {
+                    rsx!{div {class: "centered-div", "La partie est complète"}}
+                },
+                WhackGame::Play(_) => if let (Some(player), Some(session)) = (player(), session()) {
+                    let points = scores().iter().find(|s| s.color == player).map_or(0, |s| s.points);
+                    rsx!{Tap {player, session, points}}
+                } else {
+                    rsx!{div {class: "centered-div", "Une partie est en cours"}}
+                },
+                WhackGame::Ranking(_) => rsx!{Ranking {scores: scores(), names: names()}},
+            }
+        }
+    }
+}
+
+#[component]
+fn JoinWhack(
+    joined: Vec<PlayColor>,
+    mut player: Signal<Option<PlayColor>>,
+    mut session: Signal<Option<String>>,
+) -> Element {
+    let mut nickname = use_signal(String::new);
+
+    use_effect(move || {
+        player.set(None);
+        session.set(None);
+    });
+
+    let join = move |color: PlayColor| async move {
+        document::eval(include_str!("../../fullscreen.js"));
+        match whack_join(color, nickname()).await {
+            Ok(Limited::Accepted(Some(s))) => {
+                session.set(Some(s));
+                player.set(Some(color));
+            }
+            Ok(Limited::Accepted(None)) => tracing::warn!("Couldn't join with {color}"),
+            Ok(Limited::Cooldown(ms)) => tracing::warn!("Joining too fast, wait {ms}ms"),
+            Err(e) => tracing::error!("While joining: {e:?}"),
+        }
+    };
+    let free: Vec<PlayColor> = PlayColor::all()
+        .into_iter()
+        .filter(|c| c.can_join(&joined))
+        .collect();
+
+    rsx! {
+        div {
+            class: "team-row",
+            input {
+                r#type: "text",
+                class: "team-block",
+                placeholder: "Pseudo",
+                maxlength: "16",
+                value: "{nickname}",
+                oninput: move |evt| nickname.set(evt.value()),
+            }
+        }
+        div {
+            id: "player-grid",
+
+            for color in free {
+                button {onclick: move |_| async move {join(color).await},
+                    class:"color-block", style:"background-color: #{color.to_hex_pastel()};",
+                    "{color.to_string()}"
+                }
+            }
+        }
+    }
+}
+
+/// The whole screen is one button, tapped as soon as a light in the color
+/// of the player or in white shows up.
+#[component]
+fn Tap(player: PlayColor, session: String, points: i32) -> Element {
+    let session = use_signal(|| session);
+    let mut result = use_signal(|| None);
+
+    rsx! {
+        div {
+            class: "color-block whack-button",
+            style: "background-color: #{player.to_hex_pastel()};",
+            onmousedown: move |_| async move {
+                match whack_tap(session()).await {
+                    Ok(Limited::Accepted(whacked)) => result.set(whacked),
+                    Ok(Limited::Cooldown(ms)) => tracing::warn!("Tapping too fast, wait {ms}ms"),
+                    Err(e) => tracing::error!("While calling whack_tap: {e:?}"),
+                }
+            },
+            match result() {
+                Some(Whacked::Hit(ms)) => rsx!{"Touché en {ms} ms"},
+                Some(Whacked::Late) => rsx!{"Trop tard"},
+                Some(Whacked::Penalty) => rsx!{"Pénalité!"},
+                None => rsx!{"Tape!"},
+            }
+            br{}
+            "{points} points"
+        }
+    }
+}
+
+#[component]
+fn Ranking(scores: Vec<WhackScore>, names: Nicknames) -> Element {
+    let mut scores = scores;
+    scores.sort_by_key(|score| std::cmp::Reverse(score.points));
+    rsx! {
+        div {
+            class: "centered-div drop-scores",
+            for score in scores {
+                div {
+                    style: "color: #{score.color.to_hex()};",
+                    "{names.get(score.color)}: {score.points} points"
+                    if let (Some(best), Some(average)) = (score.best(), score.average()) {
+                        br{}
+                        "meilleur {best} ms, moyenne {average} ms"
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The controls of the players carry the session returned when joining.
+pub enum MessagesWhack {
+    Join(PlayColor, String),
+    Tap(String),
+    GetState,
+    Tick,
+}
+
+pub enum AnswerWhack {
+    Joined(Option<String>),
+    Tapped(Option<Whacked>),
+    State(WhackStatus),
+}
+
+/// A lit segment, for one player or for everybody if `color` is None.
+#[derive(Debug)]
+struct Light {
+    pos: f32,
+    color: Option<PlayColor>,
+    lit_at: Instant,
+    window: usize,
+    left: usize,
+}
+
+/// Lights up `LIGHTS` segments one after the other, with a random pause in between.
+/// Like in the snake game, a second player starts the countdown.
+#[derive(Debug)]
+pub struct PlatformWhack {
+    display: Display,
+    game: WhackGame,
+    countdown: usize,
+    sessions: HashMap<String, PlayColor>,
+    names: Nicknames,
+    scores: Vec<WhackScore>,
+    light: Option<Light>,
+    lights: usize,
+    window: usize,
+    pause: usize,
+    taken: Option<Instant>,
+    last_input: Instant,
+}
+
+impl PlatformWhack {
+    pub fn new() -> Self {
+        Self {
+            display: Display::new(),
+            game: WhackGame::Idle,
+            countdown: 0,
+            sessions: HashMap::new(),
+            names: Nicknames::default(),
+            scores: vec![],
+            light: None,
+            lights: 0,
+            window: WINDOW_START,
+            pause: 0,
+            taken: None,
+            last_input: Instant::now(),
+        }
+    }
+
+    pub fn get_circle(&self) -> String {
+        self.display.get_circle()
+    }
+
+    /// Nobody signed up nor plays.
+    pub fn is_idle(&self) -> bool {
+        matches!(self.game, WhackGame::Idle)
+    }
+
+    pub fn has_session(&self, session: &str) -> bool {
+        self.sessions.contains_key(session)
+    }
+
+    pub fn last_input(&self) -> Instant {
+        self.last_input
+    }
+
+    pub fn message(&mut self, msg: MessagesWhack) -> Option<AnswerWhack> {
+        if !matches!(msg, MessagesWhack::Tick | MessagesWhack::GetState) {
+            self.last_input = Instant::now();
+        }
+        match msg {
+            MessagesWhack::Join(color, name) => {
+                return Some(AnswerWhack::Joined(self.join(color, &name)));
+            }
+            MessagesWhack::Tap(session) => return Some(AnswerWhack::Tapped(self.tap(&session))),
+            MessagesWhack::GetState => {
+                return Some(AnswerWhack::State(WhackStatus {
+                    game: self.game.clone(),
+                    names: self.names.clone(),
+                    scores: self.scores.clone(),
+                }))
+            }
+            MessagesWhack::Tick => self.tick(),
+        }
+        None
+    }
+
+    fn join(&mut self, c: PlayColor, name: &str) -> Option<String> {
+        let joined = match &self.game {
+            WhackGame::Idle => vec![],
+            WhackGame::Signup(joined) if joined.len() < MAX_PLAYERS => joined.clone(),
+            _ => return None,
+        };
+        if !c.can_join(&joined) {
+            return None;
+        }
+        let joined = [joined, vec![c]].concat();
+        if joined.len() > 1 {
+            self.countdown = COUNTDOWN_PLAY;
+        }
+        self.game = WhackGame::Signup(joined);
+        let session = format!("{:032x}", rand::random::<u128>());
+        self.sessions.insert(session.clone(), c);
+        self.names.set(c, name);
+        Some(session)
+    }
+
+    /// The reaction time goes from the tick which showed the light to the tap
+    /// arriving on the server, so it is the same for every phone on the same network.
+    fn tap(&mut self, session: &str) -> Option<Whacked> {
+        let c = *self.sessions.get(session)?;
+        if !matches!(self.game, WhackGame::Play(_)) {
+            return None;
+        }
+        let score = self.scores.iter_mut().find(|s| s.color == c)?;
+        match &self.light {
+            Some(light) if light.color.is_none_or(|l| l == c) => {
+                let ms = light.lit_at.elapsed().as_millis() as u64;
+                score.points += 1;
+                score.reactions.push(ms);
+                self.light = None;
+                self.taken = Some(Instant::now());
+                self.pause = Self::random_pause();
+                Some(Whacked::Hit(ms))
+            }
+            None if self.taken.is_some_and(|t| t.elapsed() < TAP_GRACE) => Some(Whacked::Late),
+            _ => {
+                score.points -= 1;
+                Some(Whacked::Penalty)
+            }
+        }
+    }
+
+    fn random_pause() -> usize {
+        PAUSE_MIN + rand::random::<usize>() % (PAUSE_MAX - PAUSE_MIN)
+    }
+
+    /// Half of the lights are white, the others go to a random player.
+    fn light_up(&mut self, players: &[PlayColor]) {
+        let color = if rand::random() {
+            None
+        } else {
+            Some(players[rand::random::<usize>() % players.len()])
+        };
+        self.light = Some(Light {
+            pos: rand::random::<f32>() * LED_COUNT as f32,
+            color,
+            lit_at: Instant::now(),
+            window: self.window,
+            left: self.window,
+        });
+        self.window = ((self.window as f32 * WINDOW_SHRINK) as usize).max(WINDOW_MIN);
+        self.lights -= 1;
+    }
+
+    fn tick(&mut self) {
+        self.display.tick();
+
+        match self.game.clone() {
+            WhackGame::Idle => self.display.rainbow(),
+            WhackGame::Signup(players) => self.display.game_signup(players, self.countdown),
+            WhackGame::Play(players) => {
+                if let Some(light) = self.light.as_mut() {
+                    light.left -= 1;
+                    if light.left == 0 {
+                        self.light = None;
+                        self.pause = Self::random_pause();
+                    }
+                } else if self.pause > 0 {
+                    self.pause -= 1;
+                } else if self.lights > 0 {
+                    self.light_up(&players);
+                } else {
+                    self.game = WhackGame::Ranking(players);
+                    self.countdown = COUNTDOWN_RANKING;
+                }
+                // The light shrinks while its window runs out.
+                self.display.clear();
+                if let Some(light) = &self.light {
+                    let width = LIGHT_WIDTH * light.left as f32 / light.window as f32;
+                    let color = light.color.unwrap_or(WHITE);
+                    self.display
+                        .draw_blobs(Layer::Entities, vec![Blob::Drop(light.pos, color, width)]);
+                }
+            }
+            WhackGame::Ranking(_) => {
+                let ranking = self
+                    .scores
+                    .iter()
+                    .map(|s| (s.color, s.points.max(0) as usize))
+                    .collect();
+                self.display.game_ranking(ranking, self.countdown);
+            }
+        }
+
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            if self.countdown == 0 {
+                self.game = match self.game.clone() {
+                    WhackGame::Signup(players) => {
+                        self.scores = players.iter().map(|c| WhackScore::new(*c)).collect();
+                        self.lights = LIGHTS;
+                        self.window = WINDOW_START;
+                        self.pause = Self::random_pause();
+                        self.taken = None;
+                        self.display.reset();
+                        WhackGame::Play(players)
+                    }
+                    _ => {
+                        self.sessions.clear();
+                        self.names = Nicknames::default();
+                        self.scores.clear();
+                        WhackGame::Idle
+                    }
+                }
+            }
+        }
+        self.display.flatten();
+    }
+}
+
+#[server(endpoint = "whack/join")]
+async fn whack_join(
+    color: PlayColor,
+    name: String,
+) -> Result<Limited<Option<String>>, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if let Err(wait) = plat.rate_limit(Game::Whack, &crate::client_ip().await?) {
+        return Ok(Limited::Cooldown(wait.as_millis() as u64));
+    }
+    match plat.whack_message(MessagesWhack::Join(color, name)) {
+        Some(AnswerWhack::Joined(session)) => Ok(Limited::Accepted(session)),
+        _ => Ok(Limited::Accepted(None)),
+    }
+}
+
+#[server(endpoint = "whack/tap")]
+async fn whack_tap(session: String) -> Result<Limited<Option<Whacked>>, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if let Err(wait) = plat.rate_limit_session(Game::Whack, &session) {
+        return Ok(Limited::Cooldown(wait.as_millis() as u64));
+    }
+    match plat.whack_message(MessagesWhack::Tap(session)) {
+        Some(AnswerWhack::Tapped(whacked)) => Ok(Limited::Accepted(whacked)),
+        _ => Ok(Limited::Accepted(None)),
+    }
+}
+
+#[server(endpoint = "whack/state")]
+async fn whack_state() -> Result<WhackStatus, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if let Some(AnswerWhack::State(state)) = plat.whack_message(MessagesWhack::GetState) {
+        Ok(state)
+    } else {
+        Err(ServerFnError::ServerError("didn't get state".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLUE: PlayColor = PlayColor::rgb(0x00, 0x00, 0xff);
+
+    /// A game with two players, before the first light.
+    fn playing() -> (PlatformWhack, String, String) {
+        let mut platform = PlatformWhack::new();
+        let red = platform.join(PlayColor::RED, "").unwrap();
+        let blue = platform.join(BLUE, "").unwrap();
+        for _ in 0..COUNTDOWN_PLAY {
+            platform.tick();
+        }
+        assert!(matches!(platform.game, WhackGame::Play(_)));
+        (platform, red, blue)
+    }
+
+    fn light(color: Option<PlayColor>, lit_ms_ago: u64) -> Option<Light> {
+        Some(Light {
+            pos: 0.,
+            color,
+            lit_at: Instant::now() - Duration::from_millis(lit_ms_ago),
+            window: WINDOW_START,
+            left: WINDOW_START,
+        })
+    }
+
+    fn points(platform: &PlatformWhack) -> Vec<i32> {
+        platform.scores.iter().map(|s| s.points).collect()
+    }
+
+    #[test]
+    fn hit_measures_the_reaction_on_the_server() {
+        let (mut platform, red, _) = playing();
+        platform.light = light(None, 250);
+        let Some(Whacked::Hit(ms)) = platform.tap(&red) else {
+            panic!("expected a hit");
+        };
+        assert!((250..1000).contains(&ms));
+        assert_eq!(platform.scores[0].reactions, vec![ms]);
+        assert_eq!(points(&platform), vec![1, 0]);
+        assert!(platform.light.is_none());
+    }
+
+    #[test]
+    fn colored_light_only_for_its_player() {
+        let (mut platform, red, blue) = playing();
+        platform.light = light(Some(BLUE), 100);
+        assert_eq!(platform.tap(&red), Some(Whacked::Penalty));
+        assert!(matches!(platform.tap(&blue), Some(Whacked::Hit(_))));
+        assert_eq!(points(&platform), vec![-1, 1]);
+    }
+
+    #[test]
+    fn late_taps_are_not_penalized_during_the_grace() {
+        let (mut platform, red, blue) = playing();
+        platform.light = light(None, 100);
+        assert!(matches!(platform.tap(&red), Some(Whacked::Hit(_))));
+        assert_eq!(platform.tap(&blue), Some(Whacked::Late));
+        assert_eq!(points(&platform), vec![1, 0]);
+
+        platform.taken = Some(Instant::now() - TAP_GRACE);
+        assert_eq!(platform.tap(&blue), Some(Whacked::Penalty));
+        assert_eq!(points(&platform), vec![1, -1]);
+    }
+
+    #[test]
+    fn taps_outside_of_the_game_are_ignored() {
+        let mut platform = PlatformWhack::new();
+        let red = platform.join(PlayColor::RED, "").unwrap();
+        assert_eq!(platform.tap(&red), None);
+        let (mut platform, _, _) = playing();
+        assert_eq!(platform.tap("unknown"), None);
+    }
+
+    #[test]
+    fn window_shrinks_with_every_light() {
+        let (mut platform, _, _) = playing();
+        let mut windows = vec![];
+        for _ in 0..LIGHTS {
+            platform.light_up(&[PlayColor::RED, BLUE]);
+            windows.push(platform.light.as_ref().unwrap().window);
+        }
+        assert_eq!(windows[0], WINDOW_START);
+        assert_eq!(windows[1], (WINDOW_START as f32 * WINDOW_SHRINK) as usize);
+        assert!(windows.windows(2).all(|w| w[1] <= w[0]));
+        assert_eq!(windows[LIGHTS - 1], WINDOW_MIN);
+    }
+
+    #[test]
+    fn light_goes_out_after_its_window() {
+        let (mut platform, red, _) = playing();
+        platform.light = light(None, 0);
+        for _ in 0..WINDOW_START {
+            platform.tick();
+        }
+        assert!(platform.light.is_none());
+        assert_eq!(platform.tap(&red), Some(Whacked::Penalty));
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use crate::{common::Game, config::env_parse};
+
+/// Buckets not used for this long are forgotten when there are too many clients.
+const BUCKET_EXPIRY: Duration = Duration::from_secs(60);
+const MAX_BUCKETS: usize = 1000;
+
+/// Limits the requests every client can send to a game, with a token bucket
+/// per client and game.
+/// The quotas are set with `RATE_LIMIT_<GAME>` in requests per second,
+/// e.g. `RATE_LIMIT_DROP=3`, and 0 disables the limit for this game.
+/// A client can send up to one second worth of requests in a burst.
+#[derive(Debug)]
+pub struct RateLimiter {
+    quotas: HashMap<Game, f32>,
+    buckets: HashMap<(Game, String), Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f32,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn from_env() -> Self {
+        let quotas = Game::all()
+            .into_iter()
+            .map(|game| {
+                let default = match game {
+                    Game::Drop => 3.,
+                    Game::Snake | Game::Pong | Game::Simon => 20.,
+                    _ => 5.,
+                };
+                let name = format!("RATE_LIMIT_{}", game.to_string().to_uppercase());
+                (game, env_parse(&name).unwrap_or(default))
+            })
+            .collect();
+        tracing::info!("Using rate limits {quotas:?}");
+        Self {
+            quotas,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Accepts the request of the client, or returns how long it has to wait
+    /// before the next request is accepted.
+    pub fn check(&mut self, game: Game, client: &str) -> Result<(), Duration> {
+        let rate = self.quotas.get(&game).copied().unwrap_or(0.);
+        if rate <= 0. {
+            return Ok(());
+        }
+        let burst = rate.max(1.);
+        let now = Instant::now();
+        if self.buckets.len() > MAX_BUCKETS {
+            self.buckets
+                .retain(|_, b| now.duration_since(b.updated) < BUCKET_EXPIRY);
+        }
+
+        let bucket = self
+            .buckets
+            .entry((game, client.to_string()))
+            .or_insert(Bucket {
+                tokens: burst,
+                updated: now,
+            });
+        bucket.tokens =
+            (bucket.tokens + now.duration_since(bucket.updated).as_secs_f32() * rate).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1. {
+            bucket.tokens -= 1.;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f32((1. - bucket.tokens) / rate))
+        }
+    }
+}
+
+/// The proxies allowed to tell the address of the client with `X-Forwarded-For`,
+/// set in `TRUSTED_PROXIES` as comma separated addresses or networks, e.g. `172.16.0.0/12`.
+/// The header of any other peer is ignored, so phones can't make up addresses.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrustedProxies(Vec<(IpAddr, u32)>);
+
+impl TrustedProxies {
+    /// Returns the peer, or, if it's a trusted proxy, the last address of
+    /// `X-Forwarded-For` which is not a trusted proxy.
+    pub fn client(&self, peer: IpAddr, forwarded: Option<&str>) -> IpAddr {
+        let mut client = peer;
+        let mut hops = forwarded.unwrap_or_default().rsplit(',');
+        while self.trusts(client) {
+            match hops.next().and_then(|hop| hop.trim().parse().ok()) {
+                Some(hop) => client = hop,
+                None => break,
+            }
+        }
+        client
+    }
+
+    fn trusts(&self, ip: IpAddr) -> bool {
+        let same_network = |a: u128, b: u128, bits: u32, prefix: u32| {
+            let shift = bits - prefix.min(bits);
+            a.checked_shr(shift).unwrap_or(0) == b.checked_shr(shift).unwrap_or(0)
+        };
+        self.0.iter().any(|(network, prefix)| match (network, ip) {
+            (IpAddr::V4(n), IpAddr::V4(ip)) => {
+                same_network(n.to_bits().into(), ip.to_bits().into(), 32, *prefix)
+            }
+            (IpAddr::V6(n), IpAddr::V6(ip)) => {
+                same_network(n.to_bits(), ip.to_bits(), 128, *prefix)
+            }
+            _ => false,
+        })
+    }
+}
+
+impl FromStr for TrustedProxies {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .filter(|proxy| !proxy.trim().is_empty())
+            .map(|proxy| {
+                let (address, prefix) = proxy.trim().split_once('/').unwrap_or((proxy.trim(), ""));
+                let address: IpAddr = address
+                    .parse()
+                    .map_err(|e| format!("proxy '{proxy}': {e}"))?;
+                let bits = if address.is_ipv4() { 32 } else { 128 };
+                let prefix = match prefix {
+                    "" => bits,
+                    p => p
+                        .parse()
+                        .ok()
+                        .filter(|p| *p <= bits)
+                        .ok_or(format!("proxy '{proxy}': invalid prefix"))?,
+                };
+                Ok((address, prefix))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn forwarded_address_only_from_trusted_proxies() {
+        let proxies: TrustedProxies = "172.16.0.0/12, ::1".parse().unwrap();
+        let forwarded = Some("10.0.0.1, 192.168.1.7");
+        assert_eq!(
+            proxies.client(ip("172.18.0.2"), forwarded),
+            ip("192.168.1.7")
+        );
+        assert_eq!(proxies.client(ip("::1"), forwarded), ip("192.168.1.7"));
+        assert_eq!(
+            proxies.client(ip("192.168.1.8"), forwarded),
+            ip("192.168.1.8")
+        );
+        assert_eq!(proxies.client(ip("172.18.0.2"), None), ip("172.18.0.2"));
+        assert_eq!(
+            proxies.client(ip("172.18.0.2"), Some("garbage")),
+            ip("172.18.0.2")
+        );
+        assert_eq!(
+            TrustedProxies::default().client(ip("172.18.0.2"), forwarded),
+            ip("172.18.0.2")
+        );
+    }
+
+    #[test]
+    fn chained_proxies_are_skipped() {
+        let proxies: TrustedProxies = "172.16.0.0/12".parse().unwrap();
+        let forwarded = Some("1.2.3.4, 192.168.1.7, 172.20.0.3");
+        assert_eq!(
+            proxies.client(ip("172.18.0.2"), forwarded),
+            ip("192.168.1.7")
+        );
+    }
+
+    #[test]
+    fn invalid_proxies_are_rejected() {
+        assert!(TrustedProxies::from_str("172.16.0.0/33").is_err());
+        assert!(TrustedProxies::from_str("traefik").is_err());
+        assert_eq!(TrustedProxies::from_str(""), Ok(TrustedProxies::default()));
+    }
+}
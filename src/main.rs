@@ -5,14 +5,26 @@ use common::LED_COUNT;
 use dioxus::prelude::*;
 use tracing::Level;
 
-use crate::common::Game;
-use crate::games::{drop::Drop, snake::Snake};
+use crate::common::{Game, GameStatus};
+use crate::games::{
+    drop::Drop, pong::Pong, replay::Replay, simon::Simon, snake::Snake, whack::Whack,
+};
 
 mod common;
 
+#[cfg(feature = "server")]
+mod config;
 mod display;
 mod games;
 #[cfg(feature = "server")]
+mod limiter;
+#[cfg(feature = "server")]
+mod mapping;
+#[cfg(feature = "server")]
+mod recorder;
+#[cfg(feature = "server")]
+mod schedule;
+#[cfg(feature = "server")]
 mod server;
 
 #[derive(Debug, Clone, Routable, PartialEq)]
@@ -24,6 +36,8 @@ enum Route {
     Reset {},
     #[route("/display")]
     Display {},
+    #[route("/admin")]
+    Admin {},
     #[route("/:..route")]
     PageNotFound { route: Vec<String> },
 }
@@ -40,6 +54,7 @@ async fn main() {
         routing::get,
     };
     use futures::Stream;
+    use mapping::LedMapping;
     use server::Platform;
     use std::time::Duration;
     use std::{convert::Infallible, time::SystemTime};
@@ -69,7 +84,8 @@ async fn main() {
                     tracing::error!("Streaming aborted");
                     return;
                 }
-                sleep(Duration::from_millis(1000 / FREQUENCY as u64) - start.elapsed().unwrap()).await;
+                sleep(Duration::from_millis(1000 / FREQUENCY as u64) - start.elapsed().unwrap())
+                    .await;
                 tracing::info!("Elapsed: {:?}", start.elapsed());
                 start = SystemTime::now();
             }
@@ -84,6 +100,7 @@ async fn main() {
 
     let (tx, rx) = channel::<Vec<u8>>(1);
     let mut plat = platform.clone();
+    let mapping = LedMapping::from_env();
     task::spawn(async move {
         loop {
             use crate::common::FREQUENCY;
@@ -91,7 +108,7 @@ async fn main() {
             sleep(Duration::from_millis(1000 / FREQUENCY as u64)).await;
             match hex::decode(plat.get_circle()) {
                 Ok(leds) => {
-                    if let Err(e) = tx.send(leds) {
+                    if let Err(e) = tx.send(mapping.map(&leds)) {
                         tracing::error!("While sending circle data: {e:?}");
                     }
                 }
@@ -143,7 +160,7 @@ async fn main() {
             App,
         );
 
-    let router = router.into_make_service();
+    let router = router.into_make_service_with_connect_info::<std::net::SocketAddr>();
     let address = dioxus_cli_config::fullstack_address_or_localhost();
     let listener = tokio::net::TcpListener::bind(address).await.unwrap();
     axum::serve(listener, router).await.unwrap();
@@ -172,20 +189,40 @@ fn App() -> Element {
 #[component]
 fn Home() -> Element {
     let mut game = use_signal(|| Game::Idle);
+    let mut timed_out = use_signal(|| None);
+    let mut games_allowed = use_signal(|| true);
 
     use_future(move || async move {
         loop {
-            game.set(get_game().await.unwrap());
+            let status = get_game().await.unwrap();
+            game.set(status.game);
+            timed_out.set(status.timed_out);
+            games_allowed.set(status.games_allowed);
             sleep(Duration::from_millis(500)).await;
         }
     });
 
     rsx! {
         div {
+            if let Some(old) = timed_out() {
+                div {
+                    style: "text-align: center; width: 100%;",
+                    "{old} s'est arrêté faute d'activité"
+                }
+            }
             match game(){
-                Game::Idle => rsx!{Idle{game}},
+                Game::Idle => if games_allowed() {
+                    rsx!{Idle{game}}
+                } else {
+                    rsx!{div {class: "centered-div", "Les jeux sont fermés pour le moment"}}
+                },
                 Game::Snake => rsx!{Snake{}},
-                Game::Drop => rsx!{Drop{}}
+                Game::Drop => rsx!{Drop{}},
+                Game::Pong => rsx!{Pong{}},
+                Game::Whack => rsx!{Whack{}},
+                Game::Simon => rsx!{Simon{}},
+                Game::Replay => rsx!{Replay{}},
+                Game::Off => rsx!{div {class: "centered-div", "L'anneau est éteint"}},
             }
         }
     }
@@ -198,20 +235,58 @@ fn Idle(game: Signal<Game>) -> Element {
             id: "color-grid",
 
             button {onclick: move |_| async move {
-                if let Err(_) = set_game(Game::Snake).await{
-                };
+                if let Err(e) = set_game(Game::Snake, admin_secret().await).await {
+                    tracing::error!("While setting the game: {e:?}");
+                }
             },
                 class:"color-block", style:"background-color: #ffdddd;",
                 "Snake"
             }
 
             button {onclick: move |_| async move {
-                if let Err(_) = set_game(Game::Drop).await{
-                };
+                if let Err(e) = set_game(Game::Drop, admin_secret().await).await {
+                    tracing::error!("While setting the game: {e:?}");
+                }
             },
                 class:"color-block", style:"background-color: #ddffdd;",
                 "Drop"
             }
+
+            button {onclick: move |_| async move {
+                if let Err(e) = set_game(Game::Pong, admin_secret().await).await {
+                    tracing::error!("While setting the game: {e:?}");
+                }
+            },
+                class:"color-block", style:"background-color: #ffffdd;",
+                "Pong"
+            }
+
+            button {onclick: move |_| async move {
+                if let Err(e) = set_game(Game::Whack, admin_secret().await).await {
+                    tracing::error!("While setting the game: {e:?}");
+                }
+            },
+                class:"color-block", style:"background-color: #ffddff;",
+                "Whack"
+            }
+
+            button {onclick: move |_| async move {
+                if let Err(e) = set_game(Game::Simon, admin_secret().await).await {
+                    tracing::error!("While setting the game: {e:?}");
+                }
+            },
+                class:"color-block", style:"background-color: #ddffff;",
+                "Simon"
+            }
+
+            button {onclick: move |_| async move {
+                if let Err(e) = set_game(Game::Replay, admin_secret().await).await {
+                    tracing::error!("While setting the game: {e:?}");
+                }
+            },
+                class:"color-block", style:"background-color: #ddddff;",
+                "Replay"
+            }
         }
     }
 }
@@ -219,7 +294,7 @@ fn Idle(game: Signal<Game>) -> Element {
 #[component]
 fn Reset() -> Element {
     use_future(|| async {
-        if let Err(e) = set_game(Game::Idle).await {
+        if let Err(e) = set_game(Game::Idle, admin_secret().await).await {
             tracing::error!("{e:?}");
         }
         navigator().replace(Route::Home {});
@@ -234,6 +309,15 @@ fn document_eval(parts: &[&str]) {
     document::eval(&parts.join("\n"));
 }
 
+/// The admin secret is entered on the admin page and kept in the browser.
+pub async fn admin_secret() -> Option<String> {
+    document::eval("return localStorage.getItem('admin_secret');")
+        .join::<Option<String>>()
+        .await
+        .ok()
+        .flatten()
+}
+
 #[component]
 pub fn Display() -> Element {
     use_effect(move || {
@@ -256,6 +340,72 @@ pub fn Display() -> Element {
     }
 }
 
+#[component]
+fn Admin() -> Element {
+    let mut status = use_signal(String::new);
+    let mut secret = use_signal(String::new);
+
+    rsx! {
+        div {
+            class: "centered-div",
+            input {
+                r#type: "password",
+                placeholder: "Admin secret",
+                oninput: move |evt| secret.set(evt.value()),
+            }
+            button {
+                onclick: move |_| async move {
+                    let s = secret();
+                    match admin_check(Some(s.clone())).await {
+                        Ok(true) => {
+                            document::eval(&format!(
+                                "localStorage.setItem('admin_secret', {s:?});"
+                            ));
+                            status.set("Logged in".into());
+                        }
+                        Ok(false) => status.set("Wrong secret".into()),
+                        Err(e) => status.set(format!("Error: {e:?}")),
+                    }
+                },
+                "Login"
+            }
+            br{}
+            for game in Game::all() {
+                button {
+                    onclick: move |_| async move {
+                        match set_game(game, admin_secret().await).await {
+                            Ok(_) => status.set(format!("Switched to {game}")),
+                            Err(e) => status.set(format!("Error: {e:?}")),
+                        }
+                    },
+                    "Force {game}"
+                }
+            }
+            br{}
+            button {
+                onclick: move |_| async move {
+                    match record_start(admin_secret().await).await {
+                        Ok(_) => status.set("Recording...".into()),
+                        Err(e) => status.set(format!("Error: {e:?}")),
+                    }
+                },
+                "Start recording"
+            }
+            button {
+                onclick: move |_| async move {
+                    match record_stop(admin_secret().await).await {
+                        Ok(files) => status.set(format!("Recorded to {}", files.join(", "))),
+                        Err(e) => status.set(format!("Error: {e:?}")),
+                    }
+                },
+                "Stop recording"
+            }
+            br{}
+            "{status}"
+        }
+    }
+}
+
 #[component]
 fn PageNotFound(route: Vec<String>) -> Element {
     rsx! {
@@ -271,14 +421,65 @@ async fn get_circle() -> Result<String, ServerFnError> {
     Ok(plat.get_circle())
 }
 
+/// Without the admin secret, a game can only be chosen when the ring is idle,
+/// and the schedule allows games.
 #[server(endpoint = "set_game")]
-pub async fn set_game(game: Game) -> Result<Game, ServerFnError> {
+pub async fn set_game(game: Game, secret: Option<String>) -> Result<Game, ServerFnError> {
     let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    if !plat.is_idle() || !plat.games_allowed() {
+        check_admin(&plat, secret)?;
+    }
     Ok(plat.set_game(game))
 }
 
 #[server(endpoint = "get_game")]
-pub async fn get_game() -> Result<Game, ServerFnError> {
+pub async fn get_game() -> Result<GameStatus, ServerFnError> {
+    let FromContext(plat): FromContext<server::Platform> = extract().await?;
+    Ok(plat.get_status())
+}
+
+#[cfg(feature = "server")]
+fn check_admin(plat: &server::Platform, secret: Option<String>) -> Result<(), ServerFnError> {
+    if plat.is_admin(secret.as_deref()) {
+        Ok(())
+    } else {
+        Err(ServerFnError::ServerError("admin secret required".into()))
+    }
+}
+
+/// Identifies the clients without a session by their IP address,
+/// taken from `X-Forwarded-For` if the request comes through a trusted proxy.
+#[cfg(feature = "server")]
+pub async fn client_ip() -> Result<String, ServerFnError> {
     let FromContext(plat): FromContext<server::Platform> = extract().await?;
-    Ok(plat.get_game())
+    let axum::extract::ConnectInfo(addr): axum::extract::ConnectInfo<std::net::SocketAddr> =
+        extract().await?;
+    let headers: axum::http::HeaderMap = extract().await?;
+    let forwarded = headers
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok());
+    Ok(plat.client_ip(addr.ip(), forwarded).to_string())
+}
+
+#[server(endpoint = "admin/check")]
+pub async fn admin_check(secret: Option<String>) -> Result<bool, ServerFnError> {
+    let FromContext(plat): FromContext<server::Platform> = extract().await?;
+    Ok(plat.is_admin(secret.as_deref()))
+}
+
+#[server(endpoint = "admin/record_start")]
+pub async fn record_start(secret: Option<String>) -> Result<(), ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    check_admin(&plat, secret)?;
+    plat.record_start();
+    Ok(())
+}
+
+#[server(endpoint = "admin/record_stop")]
+pub async fn record_stop(secret: Option<String>) -> Result<Vec<String>, ServerFnError> {
+    let FromContext(mut plat): FromContext<server::Platform> = extract().await?;
+    check_admin(&plat, secret)?;
+    plat.record_stop()
+        .await
+        .map_err(|e| ServerFnError::ServerError(format!("While exporting: {e:?}")))
 }
@@ -0,0 +1,80 @@
+use std::env;
+
+use crate::{common::LED_COUNT, config::env_parse};
+
+/// Maps the logical ring, where LED 0 is at the top and the LEDs go clockwise,
+/// to the order of the pixels on the physical strip.
+///
+/// The configuration is read from the environment, so every installed strip
+/// can be set up without touching the firmware:
+/// - `LED_STRIP_PIXELS`: total number of pixels on the strip
+/// - `LED_FIRST_PIXEL`: pixels at the beginning of the strip which are not part of the ring
+/// - `LED_SKIP`: comma separated list of dead or hidden pixels to be left black
+/// - `LED_REVERSE`: if `true`, the strip goes counter-clockwise
+/// - `LED_START_ANGLE`: angle in degrees, clockwise from the top, of the first ring pixel
+#[derive(Debug, Clone, PartialEq)]
+pub struct LedMapping {
+    /// For every physical pixel, the logical LED it shows, or None if the pixel stays black.
+    /// It is computed once, as the strip is updated on every frame.
+    pixels: Vec<Option<usize>>,
+}
+
+impl LedMapping {
+    pub fn new(
+        strip_pixels: usize,
+        first_pixel: usize,
+        skip: &[usize],
+        reverse: bool,
+        start_angle: f32,
+    ) -> Self {
+        let ring: Vec<usize> = (first_pixel..strip_pixels)
+            .filter(|p| !skip.contains(p))
+            .collect();
+        let mut pixels = vec![None; strip_pixels];
+        let start = start_angle / 360. * LED_COUNT as f32;
+        for (k, pixel) in ring.iter().enumerate() {
+            let step = k as f32 * LED_COUNT as f32 / ring.len() as f32;
+            let logical = if reverse { start - step } else { start + step };
+            pixels[*pixel] = Some((logical.round() as i32).rem_euclid(LED_COUNT as i32) as usize);
+        }
+        Self { pixels }
+    }
+
+    pub fn from_env() -> Self {
+        let strip_pixels = env_parse("LED_STRIP_PIXELS").unwrap_or(LED_COUNT);
+        let first_pixel = env_parse("LED_FIRST_PIXEL").unwrap_or(0);
+        let reverse = env_parse("LED_REVERSE").unwrap_or(false);
+        let start_angle = env_parse("LED_START_ANGLE").unwrap_or(0.);
+        let skip: Vec<usize> = env::var("LED_SKIP")
+            .map(|skip| {
+                skip.split(',')
+                    .filter(|s| !s.trim().is_empty())
+                    .filter_map(|s| match s.trim().parse() {
+                        Ok(pixel) => Some(pixel),
+                        Err(e) => {
+                            tracing::error!("Ignoring LED_SKIP entry {s}: {e:?}");
+                            None
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        tracing::info!(
+            "Using LED mapping with {strip_pixels} pixels, first {first_pixel}, skip {skip:?}, \
+            reverse {reverse}, start angle {start_angle}"
+        );
+        Self::new(strip_pixels, first_pixel, &skip, reverse, start_angle)
+    }
+
+    /// Takes the logical LEDs as binary RGB values and returns the RGB values
+    /// in the order of the physical strip.
+    pub fn map(&self, leds: &[u8]) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|logical| match logical {
+                Some(l) if l * 3 + 3 <= leds.len() => leds[l * 3..l * 3 + 3].to_vec(),
+                _ => vec![0, 0, 0],
+            })
+            .collect()
+    }
+}
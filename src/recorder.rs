@@ -0,0 +1,126 @@
+use std::{
+    collections::VecDeque,
+    f32::consts::{FRAC_PI_2, TAU},
+    fs::{self, File},
+    io::{self, Write},
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{common::LED_COUNT, config::env_parse};
+
+const GIF_SIZE: u16 = 256;
+const GIF_LED_RADIUS: f32 = 3.;
+const GIF_FRAME_DELAY_MS: u128 = 40;
+/// The fastest color quantization, else exporting a few minutes takes ages on the Pi.
+const GIF_QUANTIZE_SPEED: i32 = 30;
+
+/// Records the frames sent to the LEDs, and writes them to `RECORD_DIR`
+/// (default `recordings`) once stopped:
+/// - `<name>.frames` with one line per frame: milliseconds since the start and the LEDs in hex
+/// - `<name>.gif` with the ring drawn as a circle of dots, like `display.js`
+///
+/// Only the last `max` of the recording are kept, so a forgotten recording doesn't fill the memory.
+#[derive(Debug)]
+pub struct Recorder {
+    start: Instant,
+    max: Duration,
+    frames: VecDeque<(u128, String)>,
+}
+
+impl Recorder {
+    pub fn new(max: Duration) -> Self {
+        Self {
+            start: Instant::now(),
+            max,
+            frames: VecDeque::new(),
+        }
+    }
+
+    pub fn add_frame(&mut self, circle: String) {
+        let now = self.start.elapsed().as_millis();
+        self.frames.push_back((now, circle));
+        while self
+            .frames
+            .front()
+            .is_some_and(|(ms, _)| now - ms > self.max.as_millis())
+        {
+            self.frames.pop_front();
+        }
+    }
+
+    /// Writes the raw frames and the GIF, and returns the paths of the written files.
+    pub fn export(&self) -> io::Result<Vec<String>> {
+        let dir = PathBuf::from(env_parse::<String>("RECORD_DIR").unwrap_or("recordings".into()));
+        fs::create_dir_all(&dir)?;
+        let name = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        let frames_path = dir.join(format!("{name}.frames"));
+        let mut file = File::create(&frames_path)?;
+        for (ms, circle) in &self.frames {
+            writeln!(file, "{ms} {circle}")?;
+        }
+
+        let gif_path = dir.join(format!("{name}.gif"));
+        self.write_gif(File::create(&gif_path)?)
+            .map_err(io::Error::other)?;
+
+        Ok(vec![frames_path, gif_path]
+            .into_iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect())
+    }
+
+    fn write_gif(&self, file: File) -> Result<(), gif::EncodingError> {
+        let mut encoder = gif::Encoder::new(file, GIF_SIZE, GIF_SIZE, &[])?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        let mut last: Option<u128> = None;
+        for (ms, circle) in &self.frames {
+            if last.is_some_and(|l| ms - l < GIF_FRAME_DELAY_MS) {
+                continue;
+            }
+            last = Some(*ms);
+            let pixels = Self::draw_ring(circle);
+            let mut frame =
+                gif::Frame::from_rgb_speed(GIF_SIZE, GIF_SIZE, &pixels, GIF_QUANTIZE_SPEED);
+            frame.delay = (GIF_FRAME_DELAY_MS / 10) as u16;
+            encoder.write_frame(&frame)?;
+        }
+        Ok(())
+    }
+
+    /// Draws the LEDs as dots on a circle, LED 0 being at the top and going clockwise.
+    fn draw_ring(circle: &str) -> Vec<u8> {
+        let size = GIF_SIZE as usize;
+        let mut pixels = vec![0u8; size * size * 3];
+        let center = size as f32 / 2.;
+        let radius = center - GIF_LED_RADIUS * 2.;
+        for i in 0..LED_COUNT {
+            let Some(rgb) = circle
+                .get(i * 6..i * 6 + 6)
+                .and_then(|h| hex::decode(h).ok())
+            else {
+                continue;
+            };
+            let angle = i as f32 / LED_COUNT as f32 * TAU - FRAC_PI_2;
+            let (x, y) = (center + angle.cos() * radius, center + angle.sin() * radius);
+            let r = GIF_LED_RADIUS.ceil() as i32;
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    if ((dx * dx + dy * dy) as f32).sqrt() > GIF_LED_RADIUS {
+                        continue;
+                    }
+                    let (px, py) = (x as i32 + dx, y as i32 + dy);
+                    if px < 0 || py < 0 || px >= size as i32 || py >= size as i32 {
+                        continue;
+                    }
+                    let p = (py as usize * size + px as usize) * 3;
+                    pixels[p..p + 3].copy_from_slice(&rgb);
+                }
+            }
+        }
+        pixels
+    }
+}
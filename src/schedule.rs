@@ -0,0 +1,194 @@
+use std::str::FromStr;
+
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+
+use crate::common::Game;
+
+/// A weekly timetable of what the ring shows, read from `SCHEDULE`.
+/// The slots are separated by `;` and written as `<days> <start>-<end> <game> [games]`:
+/// - days are comma separated, with ranges, e.g. `Mon-Fri` or `Sat,Sun`
+/// - a slot ending before its start goes past midnight, e.g. `23:00-07:00`
+/// - `games` allows the phones to choose a game during the slot
+///
+/// The first slot containing the current time is used.
+/// Outside of all slots, the phones can choose the games.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schedule {
+    slots: Vec<Slot>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slot {
+    days: Vec<Weekday>,
+    start: NaiveTime,
+    end: NaiveTime,
+    pub game: Game,
+    pub games: bool,
+}
+
+impl Schedule {
+    /// Returns the index of the slot containing the given time.
+    pub fn current(&self, now: NaiveDateTime) -> Option<usize> {
+        self.slots.iter().position(|s| s.contains(now))
+    }
+
+    pub fn slot(&self, index: usize) -> Option<&Slot> {
+        self.slots.get(index)
+    }
+}
+
+impl Slot {
+    fn contains(&self, now: NaiveDateTime) -> bool {
+        let (day, time) = (now.weekday(), now.time());
+        if self.start < self.end {
+            self.days.contains(&day) && self.start <= time && time < self.end
+        } else {
+            (self.days.contains(&day) && time >= self.start)
+                || (self.days.contains(&day.pred()) && time < self.end)
+        }
+    }
+
+    fn parse_days(s: &str) -> Result<Vec<Weekday>, String> {
+        let day = |d: &str| Weekday::from_str(d).map_err(|_| format!("unknown day {d}"));
+        let mut days = vec![];
+        for part in s.split(',') {
+            match part.split_once('-') {
+                Some((first, last)) => {
+                    let (mut d, last) = (day(first)?, day(last)?);
+                    days.push(d);
+                    while d != last {
+                        d = d.succ();
+                        days.push(d);
+                    }
+                }
+                None => days.push(day(part)?),
+            }
+        }
+        Ok(days)
+    }
+}
+
+impl FromStr for Slot {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        let (days, hours, game, flags) = match parts.as_slice() {
+            [days, hours, game, flags @ ..] => (days, hours, game, flags),
+            _ => return Err(format!("slot '{s}' needs days, hours and game")),
+        };
+        let (start, end) = hours
+            .split_once('-')
+            .ok_or(format!("hours '{hours}' need a start and an end"))?;
+        let time =
+            |t: &str| NaiveTime::parse_from_str(t, "%H:%M").map_err(|e| format!("time '{t}': {e}"));
+        Ok(Self {
+            days: Self::parse_days(days)?,
+            start: time(start)?,
+            end: time(end)?,
+            game: game.parse().map_err(|_| format!("unknown game {game}"))?,
+            games: match flags {
+                [] => false,
+                ["games"] => true,
+                _ => return Err(format!("unknown flags {flags:?}")),
+            },
+        })
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self {
+            slots: s
+                .split(';')
+                .filter(|slot| !slot.trim().is_empty())
+                .map(|slot| slot.parse())
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    /// 2024-01-01 was a Monday.
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_days_hours_and_flags() {
+        let schedule: Schedule = "Mon-Fri 08:00-12:00 Idle; Sat,Sun 10:00-18:00 Snake games;"
+            .parse()
+            .unwrap();
+        assert_eq!(schedule.slots.len(), 2);
+        let weekdays = schedule.slot(0).unwrap();
+        assert_eq!(weekdays.days.len(), 5);
+        assert_eq!(weekdays.game, Game::Idle);
+        assert!(!weekdays.games);
+        let weekend = schedule.slot(1).unwrap();
+        assert_eq!(weekend.days, vec![Weekday::Sat, Weekday::Sun]);
+        assert_eq!(weekend.game, Game::Snake);
+        assert!(weekend.games);
+        assert_eq!(Schedule::from_str("").unwrap(), Schedule::default());
+    }
+
+    #[test]
+    fn day_ranges_wrap_around_the_week() {
+        let slot: Slot = "Sat-Mon 10:00-11:00 Off".parse().unwrap();
+        assert_eq!(slot.days, vec![Weekday::Sat, Weekday::Sun, Weekday::Mon]);
+    }
+
+    #[test]
+    fn rejects_invalid_slots() {
+        assert!(Schedule::from_str("Mon 08:00-12:00").is_err());
+        assert!(Schedule::from_str("Monday-Someday 08:00-12:00 Idle").is_err());
+        assert!(Schedule::from_str("Mon 08:00 Idle").is_err());
+        assert!(Schedule::from_str("Mon 8h-12h Idle").is_err());
+        assert!(Schedule::from_str("Mon 08:00-12:00 Chess").is_err());
+        assert!(Schedule::from_str("Mon 08:00-12:00 Idle always").is_err());
+    }
+
+    #[test]
+    fn first_slot_containing_the_time_is_chosen() {
+        let schedule: Schedule = "Mon-Fri 08:00-12:00 Idle; Mon 10:00-20:00 Snake"
+            .parse()
+            .unwrap();
+        assert_eq!(schedule.current(at(1, 7, 59)), None);
+        assert_eq!(schedule.current(at(1, 8, 0)), Some(0));
+        assert_eq!(schedule.current(at(1, 11, 0)), Some(0));
+        assert_eq!(schedule.current(at(1, 12, 0)), Some(1));
+        assert_eq!(schedule.current(at(1, 20, 0)), None);
+        assert_eq!(schedule.current(at(2, 13, 0)), None);
+        assert_eq!(schedule.current(at(6, 9, 0)), None);
+    }
+
+    #[test]
+    fn slots_go_past_midnight() {
+        let schedule: Schedule = "Mon-Fri 23:00-07:00 Off".parse().unwrap();
+        assert_eq!(schedule.current(at(1, 22, 59)), None);
+        assert_eq!(schedule.current(at(1, 23, 0)), Some(0));
+        assert_eq!(schedule.current(at(2, 6, 59)), Some(0));
+        assert_eq!(schedule.current(at(2, 7, 0)), None);
+        // Friday night goes on into Saturday, but Sunday night isn't part of it.
+        assert_eq!(schedule.current(at(6, 3, 0)), Some(0));
+        assert_eq!(schedule.current(at(1, 3, 0)), None);
+    }
+
+    #[test]
+    fn sunday_night_goes_on_into_monday() {
+        let schedule: Schedule = "Sun 22:00-02:00 Off".parse().unwrap();
+        assert_eq!(schedule.current(at(7, 23, 0)), Some(0));
+        assert_eq!(schedule.current(at(8, 1, 59)), Some(0));
+        assert_eq!(schedule.current(at(8, 2, 0)), None);
+        assert_eq!(schedule.current(at(9, 1, 0)), None);
+    }
+}
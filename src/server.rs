@@ -1,48 +1,264 @@
 use std::{
+    io,
+    net::IpAddr,
     sync::{Arc, Mutex},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+use chrono::Local;
+
 use crate::{
-    common::{Game, FREQUENCY},
+    common::{Game, GameStatus, FREQUENCY},
+    config::env_parse,
+    display::Transition,
     games::{
-        drop::{AnswerDrop, MessagesDrop, PlatformDrop},
+        drop::{AnswerDrop, DropPhysics, MessagesDrop, PlatformDrop},
         idle::PlatformIdle,
-        snake_board::{AnswerSnake, MessagesSnake, PlatformSnake},
+        off::PlatformOff,
+        pong::{AnswerPong, MessagesPong, PlatformPong},
+        replay::{AnswerReplay, MessagesReplay, PlatformReplay},
+        simon::{AnswerSimon, MessagesSimon, PlatformSimon},
+        snake_board::{AnswerSnake, MatchHistory, MessagesSnake, ObstacleMix, PlatformSnake},
+        whack::{AnswerWhack, MessagesWhack, PlatformWhack},
     },
+    limiter::{RateLimiter, TrustedProxies},
+    recorder::Recorder,
+    schedule::Schedule,
 };
 
+/// How long the phones are told that the last game timed out.
+const TIMED_OUT_NOTICE: Duration = Duration::from_secs(60);
+
 #[derive(Debug)]
 pub enum GamePlatform {
     Idle(PlatformIdle),
     Snake(PlatformSnake),
     Drop(PlatformDrop),
+    Pong(PlatformPong),
+    Whack(PlatformWhack),
+    Simon(PlatformSimon),
+    Replay(PlatformReplay),
+    Off(PlatformOff),
+}
+
+/// While switching games, the old game keeps running until the transition is over.
+#[derive(Debug)]
+struct Switch {
+    old: GamePlatform,
+    counter: usize,
 }
 
+/// The transition is configured with the environment variables
+/// `TRANSITION` (Crossfade, Wipe or FadeBlack) and `TRANSITION_MS`.
+/// `RECORD_MAX_SECONDS` limits how much of a recording is kept.
+/// `ADMIN_SECRET` protects resetting, forcing games and changing settings.
+/// After `INACTIVITY_MINUTES` without input, the game falls back to the game of the
+/// `SCHEDULE`, or to `ATTRACT_GAME` outside of the schedule.
+/// `OBSTACLE_MIX` sets which obstacles appear in snake.
+/// `DROP_ELASTICITY` and `DROP_MIX` set how the drops collide, `DROP_TILT` which phones tilt them.
 #[derive(Clone, Debug)]
 pub struct Platform {
     game: Arc<Mutex<GamePlatform>>,
+    switch: Arc<Mutex<Option<Switch>>>,
+    recorder: Arc<Mutex<Option<Recorder>>>,
+    record_max: Duration,
+    limiter: Arc<Mutex<RateLimiter>>,
+    proxies: TrustedProxies,
+    history: MatchHistory,
+    transition: Transition,
+    transition_length: usize,
+    admin_secret: Option<String>,
+    inactivity: Option<Duration>,
+    attract: Game,
+    timed_out: Arc<Mutex<Option<(Game, Instant)>>>,
+    schedule: Schedule,
+    slot: Arc<Mutex<Option<usize>>>,
+    obstacle_mix: ObstacleMix,
+    drop_physics: DropPhysics,
 }
 
 impl Platform {
     pub fn new() -> Self {
         let out = Self {
             game: Arc::new(Mutex::new(GamePlatform::Idle(PlatformIdle::new()))),
+            switch: Arc::new(Mutex::new(None)),
+            recorder: Arc::new(Mutex::new(None)),
+            record_max: Duration::from_secs_f32(
+                env_parse::<f32>("RECORD_MAX_SECONDS")
+                    .unwrap_or(300.)
+                    .max(1.),
+            ),
+            limiter: Arc::new(Mutex::new(RateLimiter::from_env())),
+            proxies: env_parse("TRUSTED_PROXIES").unwrap_or_default(),
+            history: Arc::new(Mutex::new(vec![])),
+            transition: env_parse("TRANSITION").unwrap_or(Transition::Crossfade),
+            transition_length: env_parse::<usize>("TRANSITION_MS").unwrap_or(1000) * FREQUENCY
+                / 1000,
+            admin_secret: env_parse("ADMIN_SECRET"),
+            inactivity: Some(env_parse::<f32>("INACTIVITY_MINUTES").unwrap_or(10.))
+                .filter(|m| *m > 0.)
+                .map(|m| Duration::from_secs_f32(m * 60.)),
+            attract: env_parse("ATTRACT_GAME").unwrap_or(Game::Idle),
+            timed_out: Arc::new(Mutex::new(None)),
+            schedule: env_parse("SCHEDULE").unwrap_or_default(),
+            slot: Arc::new(Mutex::new(None)),
+            obstacle_mix: env_parse("OBSTACLE_MIX").unwrap_or_default(),
+            drop_physics: DropPhysics {
+                elasticity: env_parse::<f32>("DROP_ELASTICITY")
+                    .unwrap_or(DropPhysics::default().elasticity)
+                    .clamp(0., 1.),
+                mix: env_parse("DROP_MIX").unwrap_or_default(),
+                tilt: env_parse("DROP_TILT").unwrap_or(DropPhysics::default().tilt),
+            },
         };
+        if out.admin_secret.is_none() {
+            tracing::warn!("ADMIN_SECRET is not set: everybody can reset the games");
+        }
 
-        let game = out.game.clone();
+        let platform = out.clone();
 
         thread::spawn(move || loop {
-            game.lock().unwrap().tick();
+            platform.tick();
             thread::sleep(Duration::from_millis(1000 / FREQUENCY as u64));
         });
 
         out
     }
 
+    fn tick(&self) {
+        self.follow_schedule();
+        let mut timed_out = self.timed_out.lock().unwrap();
+        if timed_out.is_some_and(|(_, at)| at.elapsed() > TIMED_OUT_NOTICE) {
+            *timed_out = None;
+        }
+        drop(timed_out);
+        let last_input = {
+            let mut game = self.game.lock().unwrap();
+            game.tick();
+            game.last_input()
+        };
+        if let (Some(inactivity), Some(last)) = (self.inactivity, last_input) {
+            let (game, fallback) = (self.get_game(), self.scheduled_game());
+            if game != fallback && last.elapsed() > inactivity {
+                tracing::info!("{game} timed out, switching to {fallback}");
+                self.switch_game(fallback);
+                *self.timed_out.lock().unwrap() = Some((game, Instant::now()));
+            }
+        }
+
+        let mut switch = self.switch.lock().unwrap();
+        if let Some(s) = switch.as_mut() {
+            s.old.tick();
+            s.counter += 1;
+            if s.counter >= self.transition_length {
+                *switch = None;
+            }
+        }
+        drop(switch);
+
+        if let Some(recorder) = self.recorder.lock().unwrap().as_mut() {
+            recorder.add_frame(self.circle());
+        }
+    }
+
     pub fn get_circle(&mut self) -> String {
-        self.game.lock().unwrap().get_circle()
+        self.circle()
+    }
+
+    /// Switches to the game of the schedule when a new slot starts.
+    /// A manually chosen game overrides the schedule until the next slot,
+    /// or until it times out.
+    fn follow_schedule(&self) {
+        let current = self.schedule.current(Local::now().naive_local());
+        let mut slot = self.slot.lock().unwrap();
+        if *slot != current {
+            *slot = current;
+            drop(slot);
+            let game = self.scheduled_game();
+            tracing::info!("Schedule switches to {game}");
+            self.switch_game(game);
+            *self.timed_out.lock().unwrap() = None;
+        }
+    }
+
+    /// The game of the current slot, or the attract game outside of the schedule.
+    fn scheduled_game(&self) -> Game {
+        self.slot
+            .lock()
+            .unwrap()
+            .and_then(|i| self.schedule.slot(i))
+            .map_or(self.attract, |s| s.game)
+    }
+
+    /// Outside of the schedule, the phones can always choose a game.
+    pub fn games_allowed(&self) -> bool {
+        self.slot
+            .lock()
+            .unwrap()
+            .and_then(|i| self.schedule.slot(i))
+            .is_none_or(|s| s.games)
+    }
+
+    /// Without a configured `ADMIN_SECRET`, everybody is admin.
+    pub fn is_admin(&self, secret: Option<&str>) -> bool {
+        match &self.admin_secret {
+            Some(admin) => secret == Some(admin.as_str()),
+            None => true,
+        }
+    }
+
+    /// Returns how long the client has to wait if it sent too many requests to the game.
+    pub fn rate_limit(&self, game: Game, client: &str) -> Result<(), Duration> {
+        self.limiter.lock().unwrap().check(game, client)
+    }
+
+    /// Like `rate_limit`, but only sessions of the running game get a bucket,
+    /// so made up sessions can't fill the limiter. The game ignores them anyway.
+    pub fn rate_limit_session(&self, game: Game, session: &str) -> Result<(), Duration> {
+        if self.game.lock().unwrap().has_session(session) {
+            self.rate_limit(game, session)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The address of the phone, even behind a trusted proxy.
+    pub fn client_ip(&self, peer: IpAddr, forwarded: Option<&str>) -> IpAddr {
+        self.proxies.client(peer, forwarded)
+    }
+
+    /// The ring is idle if no match would be aborted by switching the game.
+    pub fn is_idle(&self) -> bool {
+        self.game.lock().unwrap().is_idle()
+    }
+
+    pub fn record_start(&mut self) {
+        *self.recorder.lock().unwrap() = Some(Recorder::new(self.record_max));
+    }
+
+    /// Stops the recording and returns the files it has been exported to.
+    /// The export runs outside of the lock, so the ring keeps going meanwhile.
+    pub async fn record_stop(&mut self) -> io::Result<Vec<String>> {
+        let recorder = self.recorder.lock().unwrap().take();
+        match recorder {
+            Some(recorder) => tokio::task::spawn_blocking(move || recorder.export())
+                .await
+                .map_err(io::Error::other)?,
+            None => Ok(vec![]),
+        }
+    }
+
+    fn circle(&self) -> String {
+        let new = self.game.lock().unwrap().get_circle();
+        match self.switch.lock().unwrap().as_ref() {
+            Some(s) => self.transition.mix(
+                s.counter as f32 / self.transition_length as f32,
+                &s.old.get_circle(),
+                &new,
+            ),
+            None => new,
+        }
     }
 
     pub fn snake_message(&mut self, msg: MessagesSnake) -> Option<AnswerSnake> {
@@ -53,18 +269,61 @@ impl Platform {
         self.game.lock().unwrap().drop_message(msg)
     }
 
+    pub fn pong_message(&mut self, msg: MessagesPong) -> Option<AnswerPong> {
+        self.game.lock().unwrap().pong_message(msg)
+    }
+
+    pub fn whack_message(&mut self, msg: MessagesWhack) -> Option<AnswerWhack> {
+        self.game.lock().unwrap().whack_message(msg)
+    }
+
+    pub fn simon_message(&mut self, msg: MessagesSimon) -> Option<AnswerSimon> {
+        self.game.lock().unwrap().simon_message(msg)
+    }
+
+    pub fn replay_message(&mut self, msg: MessagesReplay) -> Option<AnswerReplay> {
+        self.game.lock().unwrap().replay_message(msg)
+    }
+
     pub fn get_game(&self) -> Game {
         self.game.lock().unwrap().get_game()
     }
 
+    pub fn get_status(&self) -> GameStatus {
+        GameStatus {
+            game: self.get_game(),
+            timed_out: self.timed_out.lock().unwrap().map(|(game, _)| game),
+            games_allowed: self.games_allowed(),
+        }
+    }
+
     pub fn set_game(&mut self, game: Game) -> Game {
-        let current_game = self.game.lock().unwrap().get_game();
-        if game != current_game {
-            *self.game.lock().unwrap() = match game {
-                Game::Idle => GamePlatform::Idle(PlatformIdle::new()),
-                Game::Snake => GamePlatform::Snake(PlatformSnake::new()),
-                Game::Drop => GamePlatform::Drop(PlatformDrop::new()),
-            };
+        *self.timed_out.lock().unwrap() = None;
+        self.switch_game(game)
+    }
+
+    fn switch_game(&self, game: Game) -> Game {
+        let mut current = self.game.lock().unwrap();
+        if game != current.get_game() {
+            let old = std::mem::replace(
+                &mut *current,
+                match game {
+                    Game::Idle => GamePlatform::Idle(PlatformIdle::new()),
+                    Game::Snake => GamePlatform::Snake(PlatformSnake::new(
+                        self.history.clone(),
+                        self.obstacle_mix.clone(),
+                    )),
+                    Game::Drop => GamePlatform::Drop(PlatformDrop::new(self.drop_physics.clone())),
+                    Game::Pong => GamePlatform::Pong(PlatformPong::new()),
+                    Game::Whack => GamePlatform::Whack(PlatformWhack::new()),
+                    Game::Simon => GamePlatform::Simon(PlatformSimon::new()),
+                    Game::Replay => GamePlatform::Replay(PlatformReplay::new(self.history.clone())),
+                    Game::Off => GamePlatform::Off(PlatformOff::new()),
+                },
+            );
+            if self.transition_length > 0 {
+                *self.switch.lock().unwrap() = Some(Switch { old, counter: 0 });
+            }
         }
         game
     }
@@ -76,6 +335,47 @@ impl GamePlatform {
             GamePlatform::Idle(platform_idle) => platform_idle.get_circle(),
             GamePlatform::Snake(platform_snake) => platform_snake.get_circle(),
             GamePlatform::Drop(platform_drop) => platform_drop.get_circle(),
+            GamePlatform::Pong(platform_pong) => platform_pong.get_circle(),
+            GamePlatform::Whack(platform_whack) => platform_whack.get_circle(),
+            GamePlatform::Simon(platform_simon) => platform_simon.get_circle(),
+            GamePlatform::Replay(platform_replay) => platform_replay.get_circle(),
+            GamePlatform::Off(platform_off) => platform_off.get_circle(),
+        }
+    }
+
+    /// The games without inputs, like Idle, never time out.
+    fn last_input(&self) -> Option<Instant> {
+        match self {
+            GamePlatform::Idle(_) | GamePlatform::Off(_) => None,
+            GamePlatform::Snake(platform_snake) => Some(platform_snake.last_input()),
+            GamePlatform::Drop(platform_drop) => Some(platform_drop.last_input()),
+            GamePlatform::Pong(platform_pong) => Some(platform_pong.last_input()),
+            GamePlatform::Whack(platform_whack) => Some(platform_whack.last_input()),
+            GamePlatform::Simon(platform_simon) => Some(platform_simon.last_input()),
+            GamePlatform::Replay(platform_replay) => Some(platform_replay.last_input()),
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        match self {
+            GamePlatform::Idle(_) | GamePlatform::Off(_) => true,
+            GamePlatform::Drop(platform_drop) => platform_drop.is_idle(),
+            GamePlatform::Pong(platform_pong) => platform_pong.is_idle(),
+            GamePlatform::Whack(platform_whack) => platform_whack.is_idle(),
+            GamePlatform::Simon(platform_simon) => platform_simon.is_idle(),
+            GamePlatform::Snake(platform_snake) => platform_snake.is_idle(),
+            GamePlatform::Replay(platform_replay) => platform_replay.is_idle(),
+        }
+    }
+
+    fn has_session(&self, session: &str) -> bool {
+        match self {
+            GamePlatform::Snake(platform_snake) => platform_snake.has_session(session),
+            GamePlatform::Drop(platform_drop) => platform_drop.has_session(session),
+            GamePlatform::Pong(platform_pong) => platform_pong.has_session(session),
+            GamePlatform::Whack(platform_whack) => platform_whack.has_session(session),
+            GamePlatform::Simon(platform_simon) => platform_simon.has_session(session),
+            GamePlatform::Idle(_) | GamePlatform::Replay(_) | GamePlatform::Off(_) => false,
         }
     }
 
@@ -88,6 +388,19 @@ impl GamePlatform {
             GamePlatform::Drop(platform_drop) => {
                 platform_drop.message(MessagesDrop::Tick);
             }
+            GamePlatform::Pong(platform_pong) => {
+                platform_pong.message(MessagesPong::Tick);
+            }
+            GamePlatform::Whack(platform_whack) => {
+                platform_whack.message(MessagesWhack::Tick);
+            }
+            GamePlatform::Simon(platform_simon) => {
+                platform_simon.message(MessagesSimon::Tick);
+            }
+            GamePlatform::Replay(platform_replay) => {
+                platform_replay.message(MessagesReplay::Tick);
+            }
+            GamePlatform::Off(_) => {}
         }
     }
 
@@ -107,11 +420,48 @@ impl GamePlatform {
         }
     }
 
+    fn pong_message(&mut self, msg: MessagesPong) -> Option<AnswerPong> {
+        if let GamePlatform::Pong(pong) = self {
+            pong.message(msg)
+        } else {
+            None
+        }
+    }
+
+    fn whack_message(&mut self, msg: MessagesWhack) -> Option<AnswerWhack> {
+        if let GamePlatform::Whack(whack) = self {
+            whack.message(msg)
+        } else {
+            None
+        }
+    }
+
+    fn simon_message(&mut self, msg: MessagesSimon) -> Option<AnswerSimon> {
+        if let GamePlatform::Simon(simon) = self {
+            simon.message(msg)
+        } else {
+            None
+        }
+    }
+
+    fn replay_message(&mut self, msg: MessagesReplay) -> Option<AnswerReplay> {
+        if let GamePlatform::Replay(replay) = self {
+            replay.message(msg)
+        } else {
+            None
+        }
+    }
+
     fn get_game(&self) -> Game {
         match self {
             GamePlatform::Idle(_) => Game::Idle,
             GamePlatform::Snake(_) => Game::Snake,
             GamePlatform::Drop(_) => Game::Drop,
+            GamePlatform::Pong(_) => Game::Pong,
+            GamePlatform::Whack(_) => Game::Whack,
+            GamePlatform::Simon(_) => Game::Simon,
+            GamePlatform::Replay(_) => Game::Replay,
+            GamePlatform::Off(_) => Game::Off,
         }
     }
 }
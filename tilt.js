@@ -0,0 +1,29 @@
+// Keeps the latest tilt of the phone in window.dropTilt: the angle on the ring
+// where the drops fall to, with 0 at the top and going clockwise,
+// or null if the phone lies flat.
+const TILT_MIN = 5;
+
+function dropTiltListen() {
+    window.addEventListener('deviceorientation', (event) => {
+        const beta = event.beta || 0;
+        const gamma = event.gamma || 0;
+        window.dropTilt = Math.hypot(beta, gamma) < TILT_MIN ? null : Math.atan2(gamma, -beta);
+    });
+    window.dropTiltListening = true;
+}
+
+if (!window.dropTiltListening) {
+    if (typeof DeviceOrientationEvent !== 'undefined'
+        && typeof DeviceOrientationEvent.requestPermission === 'function') {
+        // iOS only gives the orientation after asking the user.
+        DeviceOrientationEvent.requestPermission()
+            .then((state) => {
+                if (state === 'granted') {
+                    dropTiltListen();
+                }
+            })
+            .catch(console.error);
+    } else {
+        dropTiltListen();
+    }
+}